@@ -0,0 +1,347 @@
+use nih_plug::debug::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Compact identifier for every parameter we send over OSC. This is what travels over the channel
+/// from process(), the address text is only resolved on the worker thread so the audio thread
+/// never has to allocate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamId {
+    Control,
+    Shutter,
+    Rotation,
+    BeamNumber,
+    RotSpeed,
+    BeamSize,
+    Zoom,
+    ZoomSpeed,
+    Offset,
+    /// Zero based head index into `tilt_params`
+    Head(usize, HeadField),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeadField {
+    Tilt,
+    Dimmer,
+    Red,
+    Green,
+    Blue,
+}
+
+impl HeadField {
+    pub const ALL: [HeadField; 5] = [
+        HeadField::Tilt,
+        HeadField::Dimmer,
+        HeadField::Red,
+        HeadField::Green,
+        HeadField::Blue,
+    ];
+
+    /// The `#[id]` of the field in `TiltParams`
+    pub fn id(self) -> &'static str {
+        match self {
+            HeadField::Tilt => "tilt",
+            HeadField::Dimmer => "dimmer",
+            HeadField::Red => "red",
+            HeadField::Green => "green",
+            HeadField::Blue => "blue",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HeadField::Tilt => "Tilt",
+            HeadField::Dimmer => "Dimmer",
+            HeadField::Red => "Red",
+            HeadField::Green => "Green",
+            HeadField::Blue => "Blue",
+        }
+    }
+}
+
+impl ParamId {
+    /// Global (non head) params in the order they are declared in `OsClaPhotonParams`
+    pub const GLOBALS: [ParamId; 9] = [
+        ParamId::Control,
+        ParamId::Shutter,
+        ParamId::Rotation,
+        ParamId::BeamNumber,
+        ParamId::RotSpeed,
+        ParamId::BeamSize,
+        ParamId::Zoom,
+        ParamId::ZoomSpeed,
+        ParamId::Offset,
+    ];
+
+    /// Every param we send, globals first then each head in order
+    pub fn all() -> impl Iterator<Item = ParamId> {
+        Self::GLOBALS.into_iter().chain((0..crate::NUM_PARAMS).flat_map(|index| {
+            HeadField::ALL
+                .into_iter()
+                .map(move |field| ParamId::Head(index, field))
+        }))
+    }
+
+    /// Display name of a global param, this is also what the params are created with
+    pub fn global_name(self) -> &'static str {
+        match self {
+            ParamId::Control => "Control",
+            ParamId::Shutter => "Shutter",
+            ParamId::Rotation => "Rotation",
+            ParamId::BeamNumber => "Beams Number",
+            ParamId::RotSpeed => "Rotation Speed",
+            ParamId::BeamSize => "Beams Size",
+            ParamId::Zoom => "Zoom",
+            ParamId::ZoomSpeed => "Zoom Speed",
+            ParamId::Offset => "Offset",
+            ParamId::Head(_, field) => field.name(),
+        }
+    }
+
+    /// Position in `all()`, used to index per param tables
+    pub fn index(self) -> usize {
+        match self {
+            ParamId::Head(index, field) => {
+                Self::GLOBALS.len()
+                    + index * HeadField::ALL.len()
+                    + HeadField::ALL.iter().position(|f| *f == field).unwrap_or(0)
+            }
+            _ => Self::GLOBALS.iter().position(|id| *id == self).unwrap_or(0),
+        }
+    }
+
+    /// `#[id]` of a global param, or of the field for a head
+    pub fn id(self) -> &'static str {
+        match self {
+            ParamId::Control => "control",
+            ParamId::Shutter => "shutter",
+            ParamId::Rotation => "rotation",
+            ParamId::BeamNumber => "beamNr",
+            ParamId::RotSpeed => "rotSpeed",
+            ParamId::BeamSize => "beamSz",
+            ParamId::Zoom => "zoom",
+            ParamId::ZoomSpeed => "zoomSp",
+            ParamId::Offset => "offset",
+            ParamId::Head(_, field) => field.id(),
+        }
+    }
+
+    /// The id the host sees, nested array params get a 1 based suffix
+    pub fn host_id(self) -> String {
+        match self {
+            ParamId::Head(index, field) => format!("{}_{}", field.id(), index + 1),
+            _ => self.id().to_string(),
+        }
+    }
+
+    pub fn is_enum(self) -> bool {
+        matches!(self, ParamId::Control | ParamId::Shutter | ParamId::Rotation)
+    }
+
+    pub fn group(self) -> &'static str {
+        match self {
+            //Not "control", the hierarchical scheme would give `/control/control`
+            ParamId::Control | ParamId::Shutter | ParamId::Rotation => "mode",
+            ParamId::Head(..) => "head",
+            _ => "perform",
+        }
+    }
+
+    /// Display name of the param, heads are numbered from 1 like in the host
+    pub fn name(self) -> String {
+        match self {
+            ParamId::Head(index, field) => format!("{} {}", field.name(), index + 1),
+            _ => self.global_name().to_string(),
+        }
+    }
+}
+
+/// Built in address layouts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum OscAddressScheme {
+    /// `/<base>/param/<name>` with the display name, spaces become underscores since OSC
+    /// addresses can't have them
+    #[default]
+    Legacy,
+    /// `/<base>/param/<id>` with the host param id, e.g. `beamNr` or `tilt_3`
+    Id,
+    /// `/<base>/<group>/<id>` and `/<base>/head/<n>/<field>`
+    Hierarchical,
+}
+
+impl OscAddressScheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            OscAddressScheme::Legacy => "Legacy",
+            OscAddressScheme::Id => "Id",
+            OscAddressScheme::Hierarchical => "Hierarchical",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            OscAddressScheme::Legacy => OscAddressScheme::Id,
+            OscAddressScheme::Id => OscAddressScheme::Hierarchical,
+            OscAddressScheme::Hierarchical => OscAddressScheme::Legacy,
+        }
+    }
+
+    pub fn template(self, id: ParamId) -> &'static str {
+        match (self, id) {
+            (OscAddressScheme::Legacy, _) => "{base}/param/{name}",
+            (OscAddressScheme::Id, ParamId::Head(..)) => "{base}/param/{id}_{head}",
+            (OscAddressScheme::Id, _) => "{base}/param/{id}",
+            (OscAddressScheme::Hierarchical, ParamId::Head(..)) => "{base}/head/{head}/{id}",
+            (OscAddressScheme::Hierarchical, _) => "{base}/{group}/{id}",
+        }
+    }
+}
+
+/// Which scheme to use plus per param templates that override it.
+///
+/// Templates can use `{base}`, `{group}`, `{head}` (1 based, empty for globals), `{id}` (the
+/// global or head field id) and `{name}` (the display name with underscores for spaces).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OscAddressSchema {
+    pub scheme: OscAddressScheme,
+    /// Keyed by host param id, e.g. `beamNr` or `tilt_3`
+    pub templates: HashMap<String, String>,
+}
+
+impl OscAddressSchema {
+    pub fn template(&self, id: ParamId) -> &str {
+        self.templates
+            .get(&id.host_id())
+            .map_or(self.scheme.template(id), |template| template.as_str())
+    }
+}
+
+pub fn render_address(template: &str, address_base: &str, id: ParamId) -> String {
+    let head = match id {
+        ParamId::Head(index, _) => (index + 1).to_string(),
+        _ => String::new(),
+    };
+    template
+        .replace("{base}", address_base)
+        .replace("{group}", id.group())
+        .replace("{head}", &head)
+        .replace("{id}", id.id())
+        .replace("{name}", &id.name().replace(' ', "_"))
+}
+
+/// OSC 1.0 address rules: starts with a slash, no empty parts, printable ASCII only and none of
+/// the characters reserved for pattern matching
+pub fn is_valid_osc_address(address: &str) -> bool {
+    address.starts_with('/')
+        && address[1..].split('/').all(|part| {
+            !part.is_empty()
+                && part.chars().all(|c| {
+                    c.is_ascii_graphic() && !matches!(c, '#' | '*' | ',' | '?' | '[' | ']' | '{' | '}')
+                })
+        })
+}
+
+/// The base as typed in the editor, without the leading slash. Empty is allowed and leaves the
+/// base out
+pub fn is_valid_address_base(base: &str) -> bool {
+    base.is_empty() || is_valid_osc_address(&format!("/{}", base))
+}
+
+/// Every param's address for one address base, rendered once so sending is just a lookup
+pub struct AddressTable {
+    addresses: Vec<String>,
+}
+
+impl AddressTable {
+    /// Templates that would produce an invalid address are logged and replaced by the scheme's,
+    /// then by the id scheme's which is valid for any valid address base
+    pub fn new(schema: &OscAddressSchema, address_base: &str) -> Self {
+        let addresses = ParamId::all()
+            .map(|id| {
+                let templates = [
+                    schema.template(id),
+                    schema.scheme.template(id),
+                    OscAddressScheme::Id.template(id),
+                ];
+                let mut rendered = templates
+                    .iter()
+                    .map(|template| (*template, render_address(template, address_base, id)));
+                let (template, address) = rendered.next().unwrap_or_default();
+                if is_valid_osc_address(&address) {
+                    return address;
+                }
+                nih_error!("Template {} for {} gives invalid address {}", template, id.host_id(), address);
+                rendered
+                    .find(|(_, address)| is_valid_osc_address(address))
+                    .map_or(address, |(_, address)| address)
+            })
+            .collect();
+        Self { addresses }
+    }
+
+    pub fn address(&self, id: ParamId) -> &str {
+        self.addresses
+            .get(id.index())
+            .map_or("", |address| address.as_str())
+    }
+
+    /// Reverse lookup for incoming feedback
+    pub fn find(&self, address: &str) -> Option<ParamId> {
+        ParamId::all().find(|id| self.address(*id) == address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc_addresses_follow_the_1_0_rules() {
+        assert!(is_valid_osc_address("/photon/param/zoom"));
+        assert!(is_valid_osc_address("/a"));
+        for address in ["", "/", "photon/zoom", "/photon//zoom", "/photon/", "/tilt 3", "/zoom*", "/{id}"] {
+            assert!(!is_valid_osc_address(address), "{:?} was accepted", address);
+        }
+        assert!(is_valid_address_base(""));
+        assert!(is_valid_address_base("photon/left"));
+        assert!(!is_valid_address_base("/photon"));
+    }
+
+    #[test]
+    fn templates_fill_in_every_placeholder() {
+        let head = ParamId::Head(2, HeadField::Tilt);
+        assert_eq!(render_address("{base}/{group}/{head}/{id}", "/td", head), "/td/head/3/tilt");
+        assert_eq!(render_address("{base}/param/{name}", "/td", head), "/td/param/Tilt_3");
+        assert_eq!(render_address("{base}/{group}/{id}", "", ParamId::BeamNumber), "/perform/beamNr");
+    }
+
+    #[test]
+    fn every_built_in_scheme_gives_valid_addresses() {
+        for scheme in [OscAddressScheme::Legacy, OscAddressScheme::Id, OscAddressScheme::Hierarchical] {
+            let schema = OscAddressSchema {
+                scheme,
+                ..OscAddressSchema::default()
+            };
+            let table = AddressTable::new(&schema, "/td");
+            for id in ParamId::all() {
+                assert!(is_valid_osc_address(table.address(id)), "{:?} {}", scheme, table.address(id));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_templates_fall_back_to_the_scheme() {
+        let mut schema = OscAddressSchema {
+            scheme: OscAddressScheme::Hierarchical,
+            ..OscAddressSchema::default()
+        };
+        schema.templates.insert("zoom".to_string(), "{base}/zoom level".to_string());
+        schema.templates.insert("offset".to_string(), "{base}/fx/{id}".to_string());
+        let table = AddressTable::new(&schema, "/td");
+        assert_eq!(table.address(ParamId::Zoom), "/td/perform/zoom");
+        assert_eq!(table.address(ParamId::Offset), "/td/fx/offset");
+        assert_eq!(table.address(ParamId::Control), "/td/mode/control");
+        assert_eq!(table.find("/td/fx/offset"), Some(ParamId::Offset));
+    }
+}
@@ -0,0 +1,238 @@
+use nih_plug::prelude::Buffer;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::f32::consts::{LN_2, PI};
+
+use crate::address::HeadField;
+use crate::notes::HeadOverrides;
+use crate::NUM_PARAMS;
+
+/// The input is stereo, further channels are ignored
+const MAX_CHANNELS: usize = 2;
+/// Seconds, for heads that have no band smoothing yet
+const DEFAULT_SMOOTHING: f32 = 0.05;
+
+/// Head field the bands play
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum BandTarget {
+    #[default]
+    Dimmer,
+    Tilt,
+}
+
+impl BandTarget {
+    pub fn name(self) -> &'static str {
+        match self {
+            BandTarget::Dimmer => "Dimmer",
+            BandTarget::Tilt => "Tilt",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            BandTarget::Dimmer => BandTarget::Tilt,
+            BandTarget::Tilt => BandTarget::Dimmer,
+        }
+    }
+
+    pub fn field(self) -> HeadField {
+        match self {
+            BandTarget::Dimmer => HeadField::Dimmer,
+            BandTarget::Tilt => HeadField::Tilt,
+        }
+    }
+}
+
+/// Where the bands sit, what they play and how their levels become head values
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnalyserSettings {
+    pub enabled: bool,
+    pub target: BandTarget,
+    /// Hz, bottom of the band of head 1
+    pub low: f32,
+    /// Hz, top of the band of the last head
+    pub high: f32,
+    /// dB where a band starts to rise, it reaches 1 at 0 dBFS
+    pub range: f32,
+    /// Seconds for each band level to move by about two thirds, head 1 first
+    #[serde(serialize_with = "serialize_per_head", deserialize_with = "deserialize_smoothing")]
+    pub smoothing: [f32; NUM_PARAMS],
+    /// dB added to each band, head 1 first
+    #[serde(serialize_with = "serialize_per_head", deserialize_with = "deserialize_gains")]
+    pub gains: [f32; NUM_PARAMS],
+}
+
+impl Default for AnalyserSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: BandTarget::Dimmer,
+            low: 40.0,
+            high: 16000.0,
+            range: -60.0,
+            smoothing: [DEFAULT_SMOOTHING; NUM_PARAMS],
+            gains: [0.0; NUM_PARAMS],
+        }
+    }
+}
+
+//Per head values are persisted as lists so state saved by a build with another head count still
+//loads, missing heads get the default and extra ones are dropped
+fn serialize_per_head<S: Serializer>(values: &[f32; NUM_PARAMS], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(values)
+}
+
+fn deserialize_per_head<'de, D: Deserializer<'de>>(deserializer: D, pad: f32) -> Result<[f32; NUM_PARAMS], D::Error> {
+    let values = Vec::<f32>::deserialize(deserializer)?;
+    Ok(core::array::from_fn(|head| values.get(head).copied().unwrap_or(pad)))
+}
+
+fn deserialize_smoothing<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[f32; NUM_PARAMS], D::Error> {
+    deserialize_per_head(deserializer, DEFAULT_SMOOTHING)
+}
+
+fn deserialize_gains<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[f32; NUM_PARAMS], D::Error> {
+    deserialize_per_head(deserializer, 0.0)
+}
+
+/// Band pass with 0 dB at the centre, transposed direct form II
+#[derive(Clone, Copy, Debug, Default)]
+struct Biquad {
+    b0: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    /// Silent if the centre is at or above nyquist
+    fn band_pass(center: f32, octaves: f32, sample_rate: f32) -> Self {
+        if center <= 0.0 || center >= sample_rate * 0.5 {
+            return Self::default();
+        }
+        let w0 = 2.0 * PI * center / sample_rate;
+        let alpha = w0.sin() * (LN_2 / 2.0 * octaves * w0 / w0.sin()).sinh();
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: alpha / a0,
+            b2: -alpha / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+
+    fn process(&self, state: &mut [f32; 2], input: f32) -> f32 {
+        let output = self.b0 * input + state[0];
+        state[0] = state[1] - self.a1 * output;
+        state[1] = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+/// Splits the input into one band per head, a band pass and a mean square envelope per band
+#[derive(Default)]
+pub struct Filterbank {
+    filters: [Biquad; NUM_PARAMS],
+    states: [[[f32; 2]; MAX_CHANNELS]; NUM_PARAMS],
+    //Mean square per band
+    envelopes: [f32; NUM_PARAMS],
+    //Low, high and sample rate the filters were tuned for
+    tuned: Option<(f32, f32, f32)>,
+    //Field the overrides were written to, cleared when it changes or the analyser is turned off
+    active: Option<BandTarget>,
+}
+
+impl Filterbank {
+    /// Bands are spaced evenly in octaves between low and high
+    fn tune(&mut self, low: f32, high: f32, sample_rate: f32) {
+        let low = low.max(1.0);
+        let octaves = (high.max(low) / low).log2() / NUM_PARAMS as f32;
+        for (band, filter) in self.filters.iter_mut().enumerate() {
+            let center = low * 2.0f32.powf(octaves * (band as f32 + 0.5));
+            *filter = Biquad::band_pass(center, octaves.max(0.01), sample_rate);
+        }
+        self.states = [[[0.0; 2]; MAX_CHANNELS]; NUM_PARAMS];
+        self.tuned = Some((low, high, sample_rate));
+    }
+
+    /// Follows the block and writes every band as the target field of its head, or hands the
+    /// heads back to the params when the analyser is off
+    pub fn process(
+        &mut self,
+        settings: &AnalyserSettings,
+        sample_rate: f32,
+        buffer: &mut Buffer,
+        overrides: &HeadOverrides,
+    ) {
+        if self.active.is_some_and(|target| !settings.enabled || target != settings.target) {
+            overrides.clear();
+            self.envelopes = [0.0; NUM_PARAMS];
+            self.active = None;
+        }
+        if !settings.enabled {
+            return;
+        }
+        if self.tuned != Some((settings.low.max(1.0), settings.high, sample_rate)) {
+            self.tune(settings.low, settings.high, sample_rate);
+        }
+        let coefficients: [f32; NUM_PARAMS] = core::array::from_fn(|band| {
+            let smoothing = settings.smoothing[band];
+            if smoothing > 0.0 {
+                (-1.0 / (smoothing * sample_rate)).exp()
+            } else {
+                0.0
+            }
+        });
+        for frame in buffer.iter_samples() {
+            let mut power = [0.0f32; NUM_PARAMS];
+            let mut channels = 0;
+            for (channel, sample) in frame.into_iter().take(MAX_CHANNELS).enumerate() {
+                for (band, filter) in self.filters.iter().enumerate() {
+                    let output = filter.process(&mut self.states[band][channel], *sample);
+                    power[band] += output * output;
+                }
+                channels += 1;
+            }
+            if channels == 0 {
+                continue;
+            }
+            for ((envelope, power), coefficient) in
+                self.envelopes.iter_mut().zip(power).zip(coefficients)
+            {
+                let input = power / channels as f32;
+                *envelope = input + coefficient * (*envelope - input);
+            }
+        }
+        let range = settings.range.min(-0.1);
+        let field = settings.target.field();
+        for (head, (envelope, gain)) in self.envelopes.iter().zip(settings.gains).enumerate() {
+            //Mean square, so 10 log10 for dB
+            let level_db = if *envelope > 0.0 { 10.0 * envelope.log10() } else { range };
+            overrides.set(head, field, ((level_db + gain - range) / -range).clamp(0.0, 1.0));
+        }
+        self.active = Some(settings.target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::value::{Error, SeqDeserializer};
+
+    fn load(values: Vec<f32>, pad: f32) -> [f32; NUM_PARAMS] {
+        deserialize_per_head(SeqDeserializer::<_, Error>::new(values.into_iter()), pad).unwrap()
+    }
+
+    #[test]
+    fn fewer_heads_are_padded() {
+        let loaded = load(vec![0.2, 0.3], DEFAULT_SMOOTHING);
+        assert_eq!(loaded[..2], [0.2, 0.3]);
+        assert!(loaded[2..].iter().all(|smoothing| *smoothing == DEFAULT_SMOOTHING));
+    }
+
+    #[test]
+    fn more_heads_are_truncated() {
+        let saved: Vec<f32> = (0..NUM_PARAMS + 4).map(|head| head as f32).collect();
+        let loaded = load(saved.clone(), 0.0);
+        assert_eq!(loaded[..], saved[..NUM_PARAMS]);
+    }
+}
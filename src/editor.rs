@@ -1,46 +1,315 @@
 use crossbeam_channel::Sender;
 use nih_plug::debug::*;
-use nih_plug::prelude::Editor;
+use nih_plug::prelude::{Editor, GuiContext, ParamSetter};
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::ViziaTheming;
 use nih_plug_vizia::{assets, create_vizia_editor, ViziaState};
+use parking_lot::RwLock;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::subviews::{ParamView, SettingsView};
-use crate::{OsClaPhotonParams, OscAddressBaseType, OscChannelMessageType, OscConnectionType};
+use crate::address::{
+    is_valid_address_base, is_valid_osc_address, render_address, HeadField, OscAddressSchema, ParamId,
+};
+use crate::analysis::AnalyserSettings;
+use crate::encoding::{HeadOutput, OscEnumArgs};
+use crate::follower::{FollowerRoute, FollowerRoutes, FollowerSettings};
+use crate::heartbeat::OscHeartbeat;
+use crate::mapping::{OscMapping, OscMappings};
+use crate::midi::{MidiBinding, MidiBindings, MidiControl, MidiControlKind, MidiOutputs, MidiShared};
+use crate::notes::NoteMode;
+use crate::scheduler::OutputRate;
+use crate::smoothing::{OscSmoothing, OscSmoothings};
+use crate::stats::OscStats;
+use crate::subviews::{HeadView, ParamView, SettingsView, StatusView};
+use crate::transport::{parse_host, resolve_host, HostInput, OscMulticast, OscTransport, TcpFraming};
+use crate::{
+    format_osc_address_base, OsClaPhotonParams, OscAddressBaseType, OscAddressSchemaType,
+    OscChannelMessageType, OscEnumArgsType, OscHeadOutputType, OscMappingsType,
+    OscOutputRateType, OscSmoothingsType, OscConnectionType,
+    OscDestination, OscDestinationsType, OscHeartbeatType, OscListenPortType, OscServerMessageType,
+    OscTransportType, NUM_PARAMS, enum_variant_names, is_destructive_variant,
+};
 
 /// VIZIA uses points instead of pixels for text
 const POINT_SCALE: f32 = 0.75;
 
+/// How often the labels that show worker and audio thread state are checked again
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long an armed reset waits for its confirm click
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(3);
+
 #[derive(Lens)]
 struct OsClaPhotonEditor {
     sender: Arc<Sender<OscChannelMessageType>>,
+    server_sender: Arc<Sender<OscServerMessageType>>,
     params: Arc<OsClaPhotonParams>,
+    stats: Arc<OscStats>,
+    midi: Arc<MidiShared>,
     settings: OscSettings,
-    log: Vec<String>
+    log: Vec<String>,
+    //Sets params from the head page like a host automation gesture
+    gui_context: Arc<dyn GuiContext>,
+    page: EditorPage,
+    //One flag per head, the group sliders and palette edit every selected head
+    selected_heads: Vec<bool>,
+    //What the group sliders show, the first selected head or the last value set through them.
+    //Indexed like `HeadField::ALL`
+    group_values: [f32; HeadField::ALL.len()],
+    //Reset variant waiting for its confirm click and when it was armed
+    armed_variant: Option<(ParamId, usize, Instant)>,
+}
+
+/// Pages of the editor, switched with the buttons under the title
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EditorPage {
+    #[default]
+    Settings,
+    Heads,
+}
+
+impl EditorPage {
+    pub fn name(self) -> &'static str {
+        match self {
+            EditorPage::Settings => "Settings",
+            EditorPage::Heads => "Heads",
+        }
+    }
 }
 
 pub struct OscSettings {
     pub osc_server_address: String,
+    //Editor only, why the server address can't be used, None if it can
+    pub server_address_error: Option<String>,
     pub osc_server_port: u16,
+    pub osc_transport: OscTransport,
+    pub osc_tcp_framing: TcpFraming,
+    pub osc_multicast: OscMulticast,
     pub osc_address_base: String,
+    pub osc_address_schema: OscAddressSchema,
+    //Editor only, index into `ParamId::all()` of the param the per param rows edit
+    pub selected_param: usize,
+    pub osc_head_output: HeadOutput,
+    pub osc_enum_args: OscEnumArgs,
+    pub osc_mappings: OscMappings,
+    pub osc_smoothings: OscSmoothings,
+    pub osc_output_rate: OutputRate,
+    pub osc_listen_port: Option<u16>,
+    pub osc_heartbeat: OscHeartbeat,
+    pub osc_destinations: Vec<OscDestination>,
+    pub midi_bindings: MidiBindings,
+    //Editor only, waiting for a control to bind to the selected param
+    pub midi_learning: bool,
+    pub midi_outputs: MidiOutputs,
+    pub note_mode: NoteMode,
+    pub audio_follower: FollowerSettings,
+    pub follower_routes: FollowerRoutes,
+    pub analyser: AnalyserSettings,
+}
+
+impl OscSettings {
+    /// The param the per param rows edit
+    pub fn selected_id(&self) -> ParamId {
+        ParamId::all()
+            .nth(self.selected_param)
+            .unwrap_or(ParamId::Control)
+    }
+
+    /// Mapping of the selected param, the default one if it has none
+    pub fn selected_mapping(&self) -> OscMapping {
+        self.osc_mappings
+            .params
+            .get(&self.selected_id().host_id())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Smoothing of the selected param, the default one if it has none
+    pub fn selected_smoothing(&self) -> OscSmoothing {
+        self.osc_smoothings
+            .params
+            .get(&self.selected_id().host_id())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// MIDI binding of the selected param, if it has one
+    pub fn selected_midi_binding(&self) -> Option<MidiBinding> {
+        self.midi_bindings
+            .params
+            .get(&self.selected_id().host_id())
+            .cloned()
+    }
+
+    /// Control the selected param is mirrored to, if any
+    pub fn selected_midi_output(&self) -> Option<MidiControl> {
+        self.midi_outputs
+            .params
+            .get(&self.selected_id().host_id())
+            .copied()
+    }
+
+    /// Follower route of the selected param, the default one if it has none
+    pub fn selected_follower_route(&self) -> FollowerRoute {
+        self.follower_routes
+            .params
+            .get(&self.selected_id().host_id())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Whether the template row would give a valid address
+    pub fn template_valid(&self) -> bool {
+        let id = self.selected_id();
+        is_valid_osc_address(&render_address(
+            self.osc_address_schema.template(id),
+            &format_osc_address_base(&self.osc_address_base),
+            id,
+        ))
+    }
 }
 
 pub enum OsClaPhotonEditorEvent {
     SetOscServerAddress(String),
+    //Hostname lookup finished on its own thread, the host it was started for and the result
+    HostResolved(String, Result<SocketAddr, String>),
     SetOscServerPort(u16),
     SetOscAddressBase(String),
+    SetOscListenPort(Option<u16>),
     ConnectionChange,
+    //Resend every param right now
+    SendAll,
+    CycleTransport,
+    CycleTcpFraming,
+    SetMulticastGroup(String),
+    //IP or interface name
+    SetMulticastInterface(String),
+    SetMulticastTtl(u32),
+    ToggleMulticastLoopback,
+    MulticastChange,
     AddressBaseChange,
+    CycleAddressScheme,
+    //Move the per param rows to the previous or next param
+    StepSelectedParam(isize),
+    //Empty goes back to the scheme's template
+    SetParamTemplate(String),
+    AddressSchemaChange,
+    SetMappingMin(f32),
+    SetMappingMax(f32),
+    ToggleMappingInvert,
+    CycleMappingCurve,
+    //0 turns quantizing off
+    SetMappingSteps(u32),
+    CycleMappingOutput,
+    MappingsChange,
+    CycleSmoothingStyle,
+    //Seconds, or units per second for slew
+    SetSmoothingAmount(f32),
+    SmoothingsChange,
+    CycleHeadOutput,
+    CycleOutputRate,
+    CycleEnumArg(ParamId),
+    ListenPortChange,
+    ToggleHeartbeat,
+    //Seconds
+    SetHeartbeatInterval(f32),
+    SetHeartbeatTimeout(f32),
+    SetHeartbeatResync(f32),
+    HeartbeatChange,
+    AddDestination,
+    RemoveDestination(usize),
+    ToggleDestination(usize),
+    SetDestinationAddress(usize, String),
+    SetDestinationPort(usize, u16),
+    //Empty uses the main address base
+    SetDestinationAddressBase(usize, String),
+    DestinationsChange,
+    ShowPage(EditorPage),
+    ToggleHeadSelected(usize),
+    //Select or deselect every head
+    SelectAllHeads(bool),
+    //Select only this head
+    SelectOnlyHead(usize),
+    //Set a field of every selected head
+    SetHeadsField(HeadField, f32),
+    //A palette or hex colour, red green and blue at once
+    SetHeadsColor([f32; 3]),
+    //Variant index of an enum param, resets have to be clicked twice
+    SelectEnumVariant(ParamId, usize),
+    //Start or cancel learning a control for the selected param
+    ToggleMidiLearn,
+    //Index into `ParamId::all()` the learn was started for and the control that moved
+    MidiLearned(usize, MidiControl),
+    ClearMidiBinding,
+    //Param units, variant index for enums
+    SetMidiMin(f32),
+    SetMidiMax(f32),
+    CycleMidiCurve,
+    MidiBindingsChange,
+    //Off, CC, 14-bit CC or NRPN
+    CycleMidiOutKind,
+    //0-15
+    SetMidiOutChannel(u8),
+    SetMidiOutNumber(u16),
+    MidiOutputsChange,
+    ToggleNoteMode,
+    //0-15
+    SetNoteChannel(u8),
+    SetNoteFirst(u8),
+    //Seconds
+    SetNoteAttack(f32),
+    SetNoteHold(f32),
+    SetNoteRelease(f32),
+    ToggleNoteHue,
+    NoteModeChange,
+    ToggleFollower,
+    //RMS or Peak
+    CycleFollowerMode,
+    //Seconds
+    SetFollowerAttack(f32),
+    SetFollowerRelease(f32),
+    //dB
+    SetFollowerGain(f32),
+    SetFollowerThreshold(f32),
+    FollowerChange,
+    //0-1 param units of the selected param at full output
+    SetFollowerDepth(f32),
+    ToggleFollowerInvert,
+    FollowerRoutesChange,
+    ToggleAnalyser,
+    //Dimmer or Tilt
+    CycleAnalyserTarget,
+    //Hz
+    SetAnalyserLow(f32),
+    SetAnalyserHigh(f32),
+    //dB
+    SetAnalyserRange(f32),
+    //Head index and seconds
+    SetBandSmoothing(usize, f32),
+    //Head index and dB
+    SetBandGain(usize, f32),
+    AnalyserChange,
+    //Sent by a timer while the editor is open, the status panel and follower level read state
+    //that changes outside the model
+    Refresh,
 }
 
 impl Model for OsClaPhotonEditor {
-    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         event.map(|app_event, _| match app_event {
             OsClaPhotonEditorEvent::SetOscServerAddress(ip) => {
                 nih_trace!("Edit Event {}", ip);
                 self.settings.osc_server_address = ip.clone();
-                *self.params.osc_server_address.write() = self.settings.osc_server_address.clone();
+                self.settings.server_address_error = match parse_host(ip) {
+                    HostInput::Invalid(error) => Some(error),
+                    _ => None,
+                };
+                if self.settings.server_address_error.is_none() {
+                    *self.params.osc_server_address.write() = self.settings.osc_server_address.clone();
+                }
             }
             OsClaPhotonEditorEvent::SetOscServerPort(port) => {
                 nih_trace!("Edit Event {}", port);
@@ -50,27 +319,102 @@ impl Model for OsClaPhotonEditor {
             OsClaPhotonEditorEvent::SetOscAddressBase(address) => {
                 nih_trace!("Edit Event {}", address);
                 self.settings.osc_address_base = address.clone();
-                *self.params.osc_address_base.write() = self.settings.osc_address_base.clone();
+                if is_valid_address_base(address) {
+                    *self.params.osc_address_base.write() = self.settings.osc_address_base.clone();
+                }
+            }
+            OsClaPhotonEditorEvent::SetOscListenPort(port) => {
+                nih_trace!("Edit Event {:?}", port);
+                self.settings.osc_listen_port = *port;
+                *self.params.osc_listen_port.write() = self.settings.osc_listen_port;
             }
             OsClaPhotonEditorEvent::ConnectionChange => {
-                nih_trace!(
-                    "Connection Changed {}:{}",
-                    self.settings.osc_server_address,
-                    self.settings.osc_server_port
-                );
-                self.log.push(format!("Connecting to: {}:{}", self.settings.osc_server_address, self.settings.osc_server_port));
-                let send_result =
-                    self.sender
-                    .send(OscChannelMessageType::ConnectionChange(OscConnectionType {
-                        ip: self.settings.osc_server_address.clone(),
-                        port: self.settings.osc_server_port,
-                    }));
+                match parse_host(&self.settings.osc_server_address) {
+                    HostInput::Ip(_) => self.connection_change(),
+                    HostInput::Hostname => {
+                        //Lookups can take seconds, the result comes back as HostResolved
+                        let host = self.settings.osc_server_address.clone();
+                        let port = self.settings.osc_server_port;
+                        self.log.push(format!("Resolving: {}", host));
+                        cx.spawn(move |cx| {
+                            let result = resolve_host(&host, port);
+                            let r = cx.emit(OsClaPhotonEditorEvent::HostResolved(host, result));
+                            if r.is_err() {
+                                nih_error!("Failed to send HostResolved {:?}", r.unwrap_err());
+                            }
+                        });
+                    }
+                    HostInput::Invalid(error) => {
+                        self.log.push(format!("Not connecting, {}", error));
+                        self.settings.server_address_error = Some(error);
+                    }
+                }
+            }
+            OsClaPhotonEditorEvent::HostResolved(host, result) => {
+                //Ignore lookups for an address that has been edited since
+                if *host != self.settings.osc_server_address {
+                    return;
+                }
+                match result {
+                    Ok(addr) => {
+                        self.log.push(format!("Resolved {} to {}", host, addr.ip()));
+                        self.connection_change();
+                    }
+                    Err(error) => {
+                        nih_error!("Failed to resolve {}", error);
+                        self.log.push(format!("Failed to resolve {}", error));
+                        self.settings.server_address_error = Some(error.clone());
+                    }
+                }
+            }
+            OsClaPhotonEditorEvent::SendAll => {
+                nih_trace!("Send All");
+                self.log.push(format!("Sending full state"));
+                let send_result = self.sender.send(OscChannelMessageType::SendAll);
                 if send_result.is_err() {
-                    nih_error!("Failed to send ConnectionChange update {:?}", send_result.unwrap_err());
-                    self.log.push(format!("Failed change connection"));
+                    nih_error!("Failed to send SendAll {:?}", send_result.unwrap_err());
+                    self.log.push(format!("Failed to send full state"));
                 }
             }
+            OsClaPhotonEditorEvent::CycleTransport => {
+                self.settings.osc_transport = self.settings.osc_transport.next();
+                *self.params.osc_transport.write() = self.settings.osc_transport;
+                self.transport_change();
+            }
+            OsClaPhotonEditorEvent::CycleTcpFraming => {
+                self.settings.osc_tcp_framing = self.settings.osc_tcp_framing.next();
+                *self.params.osc_tcp_framing.write() = self.settings.osc_tcp_framing;
+                self.transport_change();
+            }
+            OsClaPhotonEditorEvent::SetMulticastGroup(group) => {
+                nih_trace!("Edit Event {}", group);
+                self.settings.osc_multicast.group = group.clone();
+                *self.params.osc_multicast.write() = self.settings.osc_multicast.clone();
+            }
+            OsClaPhotonEditorEvent::SetMulticastInterface(interface) => {
+                nih_trace!("Edit Event {}", interface);
+                self.settings.osc_multicast.interface = interface.clone();
+                *self.params.osc_multicast.write() = self.settings.osc_multicast.clone();
+            }
+            OsClaPhotonEditorEvent::SetMulticastTtl(ttl) => {
+                nih_trace!("Edit Event {}", ttl);
+                self.settings.osc_multicast.ttl = *ttl;
+                *self.params.osc_multicast.write() = self.settings.osc_multicast.clone();
+            }
+            OsClaPhotonEditorEvent::ToggleMulticastLoopback => {
+                self.settings.osc_multicast.loopback = !self.settings.osc_multicast.loopback;
+                *self.params.osc_multicast.write() = self.settings.osc_multicast.clone();
+                self.transport_change();
+            }
+            OsClaPhotonEditorEvent::MulticastChange => self.transport_change(),
             OsClaPhotonEditorEvent::AddressBaseChange => {
+                if !is_valid_address_base(&self.settings.osc_address_base) {
+                    self.log.push(format!(
+                        "{} is not a valid base address, no spaces or # * , ? [ ] {{ }}",
+                        self.settings.osc_address_base
+                    ));
+                    return;
+                }
                 nih_trace!("AddressBase Changed: {}", self.settings.osc_address_base);
                 self.log.push(format!("Base Address changed to: {}", self.settings.osc_address_base));
                 let send_result = self.sender.send(OscChannelMessageType::AddressBaseChange(
@@ -82,37 +426,940 @@ impl Model for OsClaPhotonEditor {
                     nih_error!("Failed to send AddressBaseChange update {:?}", send_result.unwrap_err());
                     self.log.push(format!("Failed to update base address"));
                 }
+                let server_send_result = self.server_sender.send(OscServerMessageType::AddressBaseChange(
+                    OscAddressBaseType {
+                        address: self.settings.osc_address_base.clone(),
+                    },
+                ));
+                if server_send_result.is_err() {
+                    nih_error!("Failed to send server AddressBaseChange update {:?}", server_send_result.unwrap_err());
+                    self.log.push(format!("Failed to update feedback base address"));
+                }
+            }
+            OsClaPhotonEditorEvent::CycleAddressScheme => {
+                self.settings.osc_address_schema.scheme =
+                    self.settings.osc_address_schema.scheme.next();
+                self.address_schema_change();
+            }
+            OsClaPhotonEditorEvent::StepSelectedParam(step) => {
+                let count = ParamId::all().count() as isize;
+                self.settings.selected_param =
+                    (self.settings.selected_param as isize + step).rem_euclid(count) as usize;
+            }
+            OsClaPhotonEditorEvent::SetParamTemplate(template) => {
+                nih_trace!("Edit Event {}", template);
+                let id = self.settings.selected_id();
+                let schema = &mut self.settings.osc_address_schema;
+                if template.is_empty() || template == schema.scheme.template(id) {
+                    schema.templates.remove(&id.host_id());
+                } else {
+                    schema.templates.insert(id.host_id(), template.clone());
+                }
+                *self.params.osc_address_schema.write() = schema.clone();
+            }
+            OsClaPhotonEditorEvent::AddressSchemaChange => self.address_schema_change(),
+            OsClaPhotonEditorEvent::SetMappingMin(min) => {
+                nih_trace!("Edit Event {}", min);
+                self.edit_mapping(|mapping| mapping.min = *min);
+            }
+            OsClaPhotonEditorEvent::SetMappingMax(max) => {
+                nih_trace!("Edit Event {}", max);
+                self.edit_mapping(|mapping| mapping.max = *max);
+            }
+            OsClaPhotonEditorEvent::ToggleMappingInvert => {
+                self.edit_mapping(|mapping| mapping.invert = !mapping.invert);
+                self.mappings_change();
+            }
+            OsClaPhotonEditorEvent::CycleMappingCurve => {
+                self.edit_mapping(|mapping| mapping.curve = mapping.curve.next());
+                self.mappings_change();
+            }
+            OsClaPhotonEditorEvent::SetMappingSteps(steps) => {
+                nih_trace!("Edit Event {}", steps);
+                self.edit_mapping(|mapping| mapping.steps = *steps);
+            }
+            OsClaPhotonEditorEvent::CycleMappingOutput => {
+                self.edit_mapping(|mapping| mapping.output = mapping.output.next());
+                self.mappings_change();
+            }
+            OsClaPhotonEditorEvent::MappingsChange => self.mappings_change(),
+            OsClaPhotonEditorEvent::CycleSmoothingStyle => {
+                self.edit_smoothing(|smoothing| smoothing.style = smoothing.style.next());
+                self.smoothings_change();
+            }
+            OsClaPhotonEditorEvent::SetSmoothingAmount(amount) => {
+                nih_trace!("Edit Event {}", amount);
+                self.edit_smoothing(|smoothing| smoothing.amount = *amount);
+            }
+            OsClaPhotonEditorEvent::SmoothingsChange => self.smoothings_change(),
+            OsClaPhotonEditorEvent::CycleEnumArg(id) => {
+                if let Some(enum_arg) = self.settings.osc_enum_args.get_mut(*id) {
+                    *enum_arg = enum_arg.next();
+                    self.log.push(format!("{} sent as: {}", id.name(), enum_arg.name()));
+                }
+                *self.params.osc_enum_args.write() = self.settings.osc_enum_args.clone();
+                nih_trace!("Enum Args Changed {:?}", self.settings.osc_enum_args);
+                let send_result = self.sender.send(OscChannelMessageType::EnumArgsChange(
+                    OscEnumArgsType {
+                        enum_args: self.settings.osc_enum_args.clone(),
+                    },
+                ));
+                if send_result.is_err() {
+                    nih_error!("Failed to send EnumArgsChange update {:?}", send_result.unwrap_err());
+                    self.log.push(format!("Failed to change enum args"));
+                }
+            }
+            OsClaPhotonEditorEvent::CycleOutputRate => {
+                self.settings.osc_output_rate = self.settings.osc_output_rate.next();
+                *self.params.osc_output_rate.write() = self.settings.osc_output_rate;
+                nih_trace!("Output Rate Changed {:?}", self.settings.osc_output_rate);
+                self.log.push(format!("Output rate: {}", self.settings.osc_output_rate.name()));
+                let send_result = self.sender.send(OscChannelMessageType::OutputRateChange(
+                    OscOutputRateType {
+                        output_rate: self.settings.osc_output_rate,
+                    },
+                ));
+                if send_result.is_err() {
+                    nih_error!("Failed to send OutputRateChange update {:?}", send_result.unwrap_err());
+                    self.log.push(format!("Failed to change output rate"));
+                }
+            }
+            OsClaPhotonEditorEvent::CycleHeadOutput => {
+                self.settings.osc_head_output = self.settings.osc_head_output.next();
+                *self.params.osc_head_output.write() = self.settings.osc_head_output;
+                nih_trace!("Head Output Changed {:?}", self.settings.osc_head_output);
+                self.log.push(format!("Head output: {}", self.settings.osc_head_output.name()));
+                let send_result = self.sender.send(OscChannelMessageType::HeadOutputChange(
+                    OscHeadOutputType {
+                        head_output: self.settings.osc_head_output,
+                    },
+                ));
+                if send_result.is_err() {
+                    nih_error!("Failed to send HeadOutputChange update {:?}", send_result.unwrap_err());
+                    self.log.push(format!("Failed to change head output"));
+                }
+            }
+            OsClaPhotonEditorEvent::ListenPortChange => {
+                nih_trace!("Listen Port Changed: {:?}", self.settings.osc_listen_port);
+                match self.settings.osc_listen_port {
+                    Some(port) => self.log.push(format!("Listening for feedback on: {}", port)),
+                    None => self.log.push(format!("Feedback disabled")),
+                }
+                let send_result = self.server_sender.send(OscServerMessageType::ListenPortChange(
+                    OscListenPortType {
+                        port: self.settings.osc_listen_port,
+                    },
+                ));
+                if send_result.is_err() {
+                    nih_error!("Failed to send ListenPortChange update {:?}", send_result.unwrap_err());
+                    self.log.push(format!("Failed to change listen port"));
+                }
+            }
+            OsClaPhotonEditorEvent::ToggleHeartbeat => {
+                self.settings.osc_heartbeat.enabled = !self.settings.osc_heartbeat.enabled;
+                self.heartbeat_change();
+            }
+            OsClaPhotonEditorEvent::SetHeartbeatInterval(interval) => {
+                nih_trace!("Edit Event {}", interval);
+                self.settings.osc_heartbeat.interval = *interval;
+                *self.params.osc_heartbeat.write() = self.settings.osc_heartbeat.clone();
+            }
+            OsClaPhotonEditorEvent::SetHeartbeatTimeout(timeout) => {
+                nih_trace!("Edit Event {}", timeout);
+                self.settings.osc_heartbeat.timeout = *timeout;
+                *self.params.osc_heartbeat.write() = self.settings.osc_heartbeat.clone();
+            }
+            OsClaPhotonEditorEvent::SetHeartbeatResync(resync_after) => {
+                nih_trace!("Edit Event {}", resync_after);
+                self.settings.osc_heartbeat.resync_after = *resync_after;
+                *self.params.osc_heartbeat.write() = self.settings.osc_heartbeat.clone();
+            }
+            OsClaPhotonEditorEvent::HeartbeatChange => self.heartbeat_change(),
+            OsClaPhotonEditorEvent::AddDestination => {
+                self.settings.osc_destinations.push(OscDestination::default());
+                self.destinations_change();
+            }
+            OsClaPhotonEditorEvent::RemoveDestination(index) => {
+                if *index < self.settings.osc_destinations.len() {
+                    self.settings.osc_destinations.remove(*index);
+                    self.destinations_change();
+                }
+            }
+            OsClaPhotonEditorEvent::ToggleDestination(index) => {
+                if let Some(destination) = self.settings.osc_destinations.get_mut(*index) {
+                    destination.enabled = !destination.enabled;
+                    self.destinations_change();
+                }
+            }
+            OsClaPhotonEditorEvent::SetDestinationAddress(index, ip) => {
+                nih_trace!("Edit Event {} {}", index, ip);
+                if let Some(destination) = self.settings.osc_destinations.get_mut(*index) {
+                    destination.address = ip.clone();
+                    self.persist_destinations();
+                }
+            }
+            OsClaPhotonEditorEvent::SetDestinationPort(index, port) => {
+                nih_trace!("Edit Event {} {}", index, port);
+                if let Some(destination) = self.settings.osc_destinations.get_mut(*index) {
+                    destination.port = *port;
+                    self.persist_destinations();
+                }
+            }
+            OsClaPhotonEditorEvent::SetDestinationAddressBase(index, address) => {
+                nih_trace!("Edit Event {} {}", index, address);
+                if let Some(destination) = self.settings.osc_destinations.get_mut(*index) {
+                    destination.address_base = if address.is_empty() {
+                        None
+                    } else {
+                        Some(address.clone())
+                    };
+                    self.persist_destinations();
+                }
+            }
+            OsClaPhotonEditorEvent::DestinationsChange => self.destinations_change(),
+            OsClaPhotonEditorEvent::ShowPage(page) => self.page = *page,
+            OsClaPhotonEditorEvent::ToggleHeadSelected(index) => {
+                if let Some(selected) = self.selected_heads.get_mut(*index) {
+                    *selected = !*selected;
+                }
+                self.load_group_values();
+            }
+            OsClaPhotonEditorEvent::SelectAllHeads(selected) => {
+                self.selected_heads.iter_mut().for_each(|head| *head = *selected);
+                self.load_group_values();
+            }
+            OsClaPhotonEditorEvent::SelectOnlyHead(index) => {
+                for (head, selected) in self.selected_heads.iter_mut().enumerate() {
+                    *selected = head == *index;
+                }
+                self.load_group_values();
+            }
+            OsClaPhotonEditorEvent::SetHeadsField(field, value) => {
+                self.set_selected_heads(*field, *value);
+            }
+            OsClaPhotonEditorEvent::SetHeadsColor([red, green, blue]) => {
+                self.set_selected_heads(HeadField::Red, *red);
+                self.set_selected_heads(HeadField::Green, *green);
+                self.set_selected_heads(HeadField::Blue, *blue);
+            }
+            OsClaPhotonEditorEvent::SelectEnumVariant(id, index) => {
+                let variant = enum_variant_names(*id).get(*index).copied().unwrap_or_default();
+                if is_destructive_variant(*id, *index) {
+                    let confirmed = matches!(
+                        self.armed_variant,
+                        Some((armed_id, armed_index, armed_at))
+                            if armed_id == *id && armed_index == *index && armed_at.elapsed() < CONFIRM_TIMEOUT
+                    );
+                    if !confirmed {
+                        self.armed_variant = Some((*id, *index, Instant::now()));
+                        self.log.push(format!("Click {} again to confirm", variant));
+                        return;
+                    }
+                    self.log.push(format!("{}: {}", id.name(), variant));
+                }
+                self.armed_variant = None;
+                self.params.set_param_value(
+                    &ParamSetter::new(self.gui_context.as_ref()),
+                    *id,
+                    *index as f32,
+                );
+            }
+            OsClaPhotonEditorEvent::ToggleMidiLearn => {
+                if self.settings.midi_learning {
+                    self.midi.stop_learn();
+                    self.settings.midi_learning = false;
+                    self.log.push(format!("MIDI learn cancelled"));
+                    return;
+                }
+                self.settings.midi_learning = true;
+                self.midi.start_learn(self.settings.selected_param);
+                self.log.push(format!(
+                    "Move a control to bind it to {}",
+                    self.settings.selected_id().name()
+                ));
+                //process() sees the control, this waits for it without blocking the GUI
+                let midi = self.midi.clone();
+                cx.spawn(move |cx| {
+                    if let Some((param, control)) = midi.wait_for_learn() {
+                        let r = cx.emit(OsClaPhotonEditorEvent::MidiLearned(param, control));
+                        if r.is_err() {
+                            nih_error!("Failed to send MidiLearned {:?}", r.unwrap_err());
+                        }
+                    }
+                });
+            }
+            OsClaPhotonEditorEvent::MidiLearned(param, control) => {
+                self.settings.midi_learning = false;
+                if let Some(id) = ParamId::all().nth(*param) {
+                    self.settings
+                        .midi_bindings
+                        .params
+                        .insert(id.host_id(), MidiBinding::new(id, *control));
+                    self.log.push(format!("{} bound to {}", id.name(), control.name()));
+                    self.midi_bindings_change();
+                }
+            }
+            OsClaPhotonEditorEvent::ClearMidiBinding => {
+                let id = self.settings.selected_id();
+                if self.settings.midi_bindings.params.remove(&id.host_id()).is_some() {
+                    self.log.push(format!("{} unbound", id.name()));
+                    self.midi_bindings_change();
+                }
+            }
+            OsClaPhotonEditorEvent::SetMidiMin(min) => {
+                nih_trace!("Edit Event {}", min);
+                self.edit_midi_binding(|binding| binding.min = *min);
+            }
+            OsClaPhotonEditorEvent::SetMidiMax(max) => {
+                nih_trace!("Edit Event {}", max);
+                self.edit_midi_binding(|binding| binding.max = *max);
+            }
+            OsClaPhotonEditorEvent::CycleMidiCurve => {
+                self.edit_midi_binding(|binding| binding.curve = binding.curve.next());
+                self.midi_bindings_change();
+            }
+            OsClaPhotonEditorEvent::MidiBindingsChange => self.midi_bindings_change(),
+            OsClaPhotonEditorEvent::CycleMidiOutKind => {
+                let id = self.settings.selected_id();
+                let outputs = &mut self.settings.midi_outputs.params;
+                let current = outputs.get(&id.host_id()).copied();
+                match MidiControlKind::next(current.map(|control| control.kind)) {
+                    Some(kind) => {
+                        let control = current.unwrap_or(MidiControl {
+                            kind,
+                            channel: 0,
+                            number: 0,
+                        });
+                        outputs.insert(
+                            id.host_id(),
+                            MidiControl {
+                                kind,
+                                number: control.number.min(kind.max_number()),
+                                ..control
+                            },
+                        );
+                    }
+                    None => {
+                        outputs.remove(&id.host_id());
+                    }
+                }
+                self.midi_outputs_change();
+            }
+            OsClaPhotonEditorEvent::SetMidiOutChannel(channel) => {
+                nih_trace!("Edit Event {}", channel);
+                self.edit_midi_output(|control| control.channel = *channel);
+            }
+            OsClaPhotonEditorEvent::SetMidiOutNumber(number) => {
+                nih_trace!("Edit Event {}", number);
+                self.edit_midi_output(|control| control.number = (*number).min(control.kind.max_number()));
+            }
+            OsClaPhotonEditorEvent::MidiOutputsChange => self.midi_outputs_change(),
+            OsClaPhotonEditorEvent::ToggleNoteMode => {
+                self.settings.note_mode.enabled = !self.settings.note_mode.enabled;
+                self.note_mode_change();
+            }
+            OsClaPhotonEditorEvent::SetNoteChannel(channel) => {
+                nih_trace!("Edit Event {}", channel);
+                self.settings.note_mode.channel = *channel;
+                *self.params.note_mode.write() = self.settings.note_mode;
+            }
+            OsClaPhotonEditorEvent::SetNoteFirst(note) => {
+                nih_trace!("Edit Event {}", note);
+                self.settings.note_mode.first_note = *note;
+                *self.params.note_mode.write() = self.settings.note_mode;
+            }
+            OsClaPhotonEditorEvent::SetNoteAttack(attack) => {
+                nih_trace!("Edit Event {}", attack);
+                self.settings.note_mode.attack = *attack;
+                *self.params.note_mode.write() = self.settings.note_mode;
+            }
+            OsClaPhotonEditorEvent::SetNoteHold(hold) => {
+                nih_trace!("Edit Event {}", hold);
+                self.settings.note_mode.hold = *hold;
+                *self.params.note_mode.write() = self.settings.note_mode;
+            }
+            OsClaPhotonEditorEvent::SetNoteRelease(release) => {
+                nih_trace!("Edit Event {}", release);
+                self.settings.note_mode.release = *release;
+                *self.params.note_mode.write() = self.settings.note_mode;
+            }
+            OsClaPhotonEditorEvent::ToggleNoteHue => {
+                self.settings.note_mode.hue = !self.settings.note_mode.hue;
+                self.note_mode_change();
+            }
+            OsClaPhotonEditorEvent::NoteModeChange => self.note_mode_change(),
+            OsClaPhotonEditorEvent::ToggleFollower => {
+                self.settings.audio_follower.enabled = !self.settings.audio_follower.enabled;
+                self.follower_change();
+            }
+            OsClaPhotonEditorEvent::CycleFollowerMode => {
+                self.settings.audio_follower.mode = self.settings.audio_follower.mode.next();
+                self.follower_change();
+            }
+            OsClaPhotonEditorEvent::SetFollowerAttack(attack) => {
+                nih_trace!("Edit Event {}", attack);
+                self.settings.audio_follower.attack = *attack;
+                *self.params.audio_follower.write() = self.settings.audio_follower;
+            }
+            OsClaPhotonEditorEvent::SetFollowerRelease(release) => {
+                nih_trace!("Edit Event {}", release);
+                self.settings.audio_follower.release = *release;
+                *self.params.audio_follower.write() = self.settings.audio_follower;
+            }
+            OsClaPhotonEditorEvent::SetFollowerGain(gain) => {
+                nih_trace!("Edit Event {}", gain);
+                self.settings.audio_follower.gain = *gain;
+                *self.params.audio_follower.write() = self.settings.audio_follower;
+            }
+            OsClaPhotonEditorEvent::SetFollowerThreshold(threshold) => {
+                nih_trace!("Edit Event {}", threshold);
+                self.settings.audio_follower.threshold = *threshold;
+                *self.params.audio_follower.write() = self.settings.audio_follower;
+            }
+            OsClaPhotonEditorEvent::FollowerChange => self.follower_change(),
+            OsClaPhotonEditorEvent::SetFollowerDepth(depth) => {
+                nih_trace!("Edit Event {}", depth);
+                self.edit_follower_route(|route| route.depth = *depth);
+            }
+            OsClaPhotonEditorEvent::ToggleFollowerInvert => {
+                self.edit_follower_route(|route| route.invert = !route.invert);
+                self.follower_routes_change();
+            }
+            OsClaPhotonEditorEvent::FollowerRoutesChange => self.follower_routes_change(),
+            OsClaPhotonEditorEvent::ToggleAnalyser => {
+                self.settings.analyser.enabled = !self.settings.analyser.enabled;
+                self.analyser_change();
+            }
+            OsClaPhotonEditorEvent::CycleAnalyserTarget => {
+                self.settings.analyser.target = self.settings.analyser.target.next();
+                self.analyser_change();
+            }
+            OsClaPhotonEditorEvent::SetAnalyserLow(low) => {
+                nih_trace!("Edit Event {}", low);
+                self.settings.analyser.low = *low;
+                *self.params.analyser.write() = self.settings.analyser;
+            }
+            OsClaPhotonEditorEvent::SetAnalyserHigh(high) => {
+                nih_trace!("Edit Event {}", high);
+                self.settings.analyser.high = *high;
+                *self.params.analyser.write() = self.settings.analyser;
+            }
+            OsClaPhotonEditorEvent::SetAnalyserRange(range) => {
+                nih_trace!("Edit Event {}", range);
+                self.settings.analyser.range = *range;
+                *self.params.analyser.write() = self.settings.analyser;
+            }
+            OsClaPhotonEditorEvent::SetBandSmoothing(index, smoothing) => {
+                nih_trace!("Edit Event {} {}", index, smoothing);
+                if let Some(band_smoothing) = self.settings.analyser.smoothing.get_mut(*index) {
+                    *band_smoothing = *smoothing;
+                }
+                *self.params.analyser.write() = self.settings.analyser;
+            }
+            OsClaPhotonEditorEvent::SetBandGain(index, gain) => {
+                nih_trace!("Edit Event {} {}", index, gain);
+                if let Some(band_gain) = self.settings.analyser.gains.get_mut(*index) {
+                    *band_gain = *gain;
+                }
+                *self.params.analyser.write() = self.settings.analyser;
+            }
+            OsClaPhotonEditorEvent::AnalyserChange => self.analyser_change(),
+            //Handling any event has the bindings checked again, besides that only a reset that
+            //wasn't confirmed in time has to go back to its name
+            OsClaPhotonEditorEvent::Refresh => {
+                let expired = matches!(
+                    self.armed_variant,
+                    Some((_, _, armed_at)) if armed_at.elapsed() >= CONFIRM_TIMEOUT
+                );
+                if expired {
+                    self.armed_variant = None;
+                }
             }
         });
     }
 }
 
+/// The model goes away with the editor window. A learn still running would otherwise keep
+/// process() swallowing every control with nobody left to pick up what it learned
+impl Drop for OsClaPhotonEditor {
+    fn drop(&mut self) {
+        self.midi.stop_learn();
+    }
+}
+
+impl OsClaPhotonEditor {
+    fn connection_change(&mut self) {
+        nih_trace!(
+            "Connection Changed {}:{}",
+            self.settings.osc_server_address,
+            self.settings.osc_server_port
+        );
+        self.log.push(format!("Connecting to: {}:{}", self.settings.osc_server_address, self.settings.osc_server_port));
+        let send_result =
+            self.sender
+            .send(OscChannelMessageType::ConnectionChange(OscConnectionType {
+                ip: self.settings.osc_server_address.clone(),
+                port: self.settings.osc_server_port,
+            }));
+        if send_result.is_err() {
+            nih_error!("Failed to send ConnectionChange update {:?}", send_result.unwrap_err());
+            self.log.push(format!("Failed change connection"));
+        }
+    }
+
+    /// Show the current values of the first selected head in the group sliders, they keep the
+    /// last values when nothing is selected
+    fn load_group_values(&mut self) {
+        let first = self.selected_heads.iter().position(|selected| *selected);
+        if let Some(tilt_params) = first.and_then(|index| self.params.tilt_params.get(index)) {
+            for field in HeadField::ALL {
+                self.group_values[field as usize] = tilt_params.field(field).value();
+            }
+        }
+    }
+
+    /// Set a field of every selected head through the host, one gesture per head
+    fn set_selected_heads(&mut self, field: HeadField, value: f32) {
+        self.group_values[field as usize] = value;
+        let setter = ParamSetter::new(self.gui_context.as_ref());
+        for (index, selected) in self.selected_heads.iter().enumerate() {
+            if *selected {
+                self.params
+                    .set_param_value(&setter, ParamId::Head(index, field), value);
+            }
+        }
+    }
+
+    fn transport_change(&mut self) {
+        nih_trace!(
+            "Transport Changed {:?} {:?}",
+            self.settings.osc_transport,
+            self.settings.osc_tcp_framing
+        );
+        match self.settings.osc_transport {
+            OscTransport::Tcp => self.log.push(format!(
+                "Transport: {} {}",
+                self.settings.osc_transport.name(),
+                self.settings.osc_tcp_framing.name()
+            )),
+            OscTransport::UdpMulticast => self.log.push(format!(
+                "Transport: {} {}:{} TTL {}",
+                self.settings.osc_transport.name(),
+                self.settings.osc_multicast.group,
+                self.settings.osc_server_port,
+                self.settings.osc_multicast.ttl
+            )),
+            _ => self.log.push(format!("Transport: {}", self.settings.osc_transport.name())),
+        }
+        let transport = OscTransportType {
+            transport: self.settings.osc_transport,
+            tcp_framing: self.settings.osc_tcp_framing,
+            multicast: self.settings.osc_multicast.clone(),
+        };
+        let send_result = self.sender.send(OscChannelMessageType::TransportChange(transport.clone()));
+        if send_result.is_err() {
+            nih_error!("Failed to send TransportChange update {:?}", send_result.unwrap_err());
+            self.log.push(format!("Failed to change transport"));
+        }
+        let server_send_result = self.server_sender.send(OscServerMessageType::TransportChange(transport));
+        if server_send_result.is_err() {
+            nih_error!("Failed to send server TransportChange update {:?}", server_send_result.unwrap_err());
+            self.log.push(format!("Failed to change feedback transport"));
+        }
+    }
+
+    /// Change the selected param's mapping and persist it, a mapping back at the defaults is removed
+    fn edit_mapping(&mut self, edit: impl FnOnce(&mut OscMapping)) {
+        let id = self.settings.selected_id();
+        if id.is_enum() {
+            return;
+        }
+        let mappings = &mut self.settings.osc_mappings.params;
+        let mapping = mappings.entry(id.host_id()).or_default();
+        edit(mapping);
+        if *mapping == OscMapping::default() {
+            mappings.remove(&id.host_id());
+        }
+        *self.params.osc_mappings.write() = self.settings.osc_mappings.clone();
+    }
+
+    fn mappings_change(&mut self) {
+        *self.params.osc_mappings.write() = self.settings.osc_mappings.clone();
+        let id = self.settings.selected_id();
+        let mapping = self.settings.selected_mapping();
+        nih_trace!("Mappings Changed {:?}", self.settings.osc_mappings);
+        self.log.push(format!(
+            "{} mapped to {}..{} {}",
+            id.name(),
+            mapping.min,
+            mapping.max,
+            mapping.curve.name()
+        ));
+        let mappings = OscMappingsType {
+            mappings: self.settings.osc_mappings.clone(),
+        };
+        let send_result = self
+            .sender
+            .send(OscChannelMessageType::MappingsChange(mappings.clone()));
+        if send_result.is_err() {
+            nih_error!("Failed to send MappingsChange update {:?}", send_result.unwrap_err());
+            self.log.push(format!("Failed to update mappings"));
+        }
+        let server_send_result = self
+            .server_sender
+            .send(OscServerMessageType::MappingsChange(mappings));
+        if server_send_result.is_err() {
+            nih_error!("Failed to send server MappingsChange update {:?}", server_send_result.unwrap_err());
+            self.log.push(format!("Failed to update feedback mappings"));
+        }
+    }
+
+    /// Same as `edit_mapping` for the smoothing
+    fn edit_smoothing(&mut self, edit: impl FnOnce(&mut OscSmoothing)) {
+        let id = self.settings.selected_id();
+        if id.is_enum() {
+            return;
+        }
+        let smoothings = &mut self.settings.osc_smoothings.params;
+        let smoothing = smoothings.entry(id.host_id()).or_default();
+        edit(smoothing);
+        if *smoothing == OscSmoothing::default() {
+            smoothings.remove(&id.host_id());
+        }
+        *self.params.osc_smoothings.write() = self.settings.osc_smoothings.clone();
+    }
+
+    fn smoothings_change(&mut self) {
+        *self.params.osc_smoothings.write() = self.settings.osc_smoothings.clone();
+        let id = self.settings.selected_id();
+        let smoothing = self.settings.selected_smoothing();
+        nih_trace!("Smoothings Changed {:?}", self.settings.osc_smoothings);
+        self.log.push(format!(
+            "{} smoothing: {} {}",
+            id.name(),
+            smoothing.style.name(),
+            smoothing.amount
+        ));
+        let send_result = self.sender.send(OscChannelMessageType::SmoothingsChange(
+            OscSmoothingsType {
+                smoothings: self.settings.osc_smoothings.clone(),
+            },
+        ));
+        if send_result.is_err() {
+            nih_error!("Failed to send SmoothingsChange update {:?}", send_result.unwrap_err());
+            self.log.push(format!("Failed to update smoothing"));
+        }
+    }
+
+    /// Change the selected param's MIDI binding, does nothing if it has none
+    fn edit_midi_binding(&mut self, edit: impl FnOnce(&mut MidiBinding)) {
+        let id = self.settings.selected_id();
+        if let Some(binding) = self.settings.midi_bindings.params.get_mut(&id.host_id()) {
+            edit(binding);
+        }
+        *self.params.midi_bindings.write() = self.settings.midi_bindings.clone();
+    }
+
+    /// Persist the bindings and hand them to process()
+    fn midi_bindings_change(&mut self) {
+        *self.params.midi_bindings.write() = self.settings.midi_bindings.clone();
+        nih_trace!("MIDI Bindings Changed {:?}", self.settings.midi_bindings);
+        self.midi.set_bindings(&self.settings.midi_bindings);
+    }
+
+    /// Change the control the selected param is mirrored to, does nothing if it has none
+    fn edit_midi_output(&mut self, edit: impl FnOnce(&mut MidiControl)) {
+        let id = self.settings.selected_id();
+        if let Some(control) = self.settings.midi_outputs.params.get_mut(&id.host_id()) {
+            edit(control);
+        }
+        *self.params.midi_outputs.write() = self.settings.midi_outputs.clone();
+    }
+
+    /// Persist the mirrored params and hand them to process()
+    fn midi_outputs_change(&mut self) {
+        *self.params.midi_outputs.write() = self.settings.midi_outputs.clone();
+        let id = self.settings.selected_id();
+        nih_trace!("MIDI Outputs Changed {:?}", self.settings.midi_outputs);
+        match self.settings.selected_midi_output() {
+            Some(control) => self.log.push(format!("{} mirrored to {}", id.name(), control.name())),
+            None => self.log.push(format!("{} not mirrored", id.name())),
+        }
+        self.midi.set_outputs(&self.settings.midi_outputs);
+    }
+
+    /// Persist the note mode, process() copies it at the start of its next block
+    fn note_mode_change(&mut self) {
+        *self.params.note_mode.write() = self.settings.note_mode;
+        let note_mode = &self.settings.note_mode;
+        nih_trace!("Note Mode Changed {:?}", note_mode);
+        if note_mode.enabled {
+            self.log.push(format!(
+                "Note mode on channel {} from note {}, envelope {}/{}/{} s",
+                note_mode.channel + 1,
+                note_mode.first_note,
+                note_mode.attack,
+                note_mode.hold,
+                note_mode.release
+            ));
+        } else {
+            self.log.push(format!("Note mode off"));
+        }
+    }
+
+    /// Persist the follower settings, the routes are handed over separately
+    fn follower_change(&mut self) {
+        *self.params.audio_follower.write() = self.settings.audio_follower;
+        let follower = &self.settings.audio_follower;
+        nih_trace!("Follower Changed {:?}", follower);
+        if follower.enabled {
+            self.log.push(format!(
+                "Follower {} {}/{} s, gain {} dB, threshold {} dB",
+                follower.mode.name(),
+                follower.attack,
+                follower.release,
+                follower.gain,
+                follower.threshold
+            ));
+        } else {
+            self.log.push(format!("Follower off"));
+        }
+    }
+
+    /// Change the selected param's follower route, routes with no depth are removed. Enums can't
+    /// be routed
+    fn edit_follower_route(&mut self, edit: impl FnOnce(&mut FollowerRoute)) {
+        let id = self.settings.selected_id();
+        if id.is_enum() {
+            return;
+        }
+        let mut route = self.settings.selected_follower_route();
+        edit(&mut route);
+        if route.depth == 0.0 {
+            self.settings.follower_routes.params.remove(&id.host_id());
+        } else {
+            self.settings.follower_routes.params.insert(id.host_id(), route);
+        }
+        *self.params.follower_routes.write() = self.settings.follower_routes.clone();
+    }
+
+    /// Persist the routes and hand them to process()
+    fn follower_routes_change(&mut self) {
+        *self.params.follower_routes.write() = self.settings.follower_routes.clone();
+        let id = self.settings.selected_id();
+        nih_trace!("Follower Routes Changed {:?}", self.settings.follower_routes);
+        let route = self.settings.selected_follower_route();
+        if route.depth == 0.0 {
+            self.log.push(format!("{} not following", id.name()));
+        } else {
+            self.log.push(format!(
+                "{} follows with depth {}{}",
+                id.name(),
+                if route.invert { "-" } else { "" },
+                route.depth
+            ));
+        }
+        self.params.modulation.set_routes(&self.settings.follower_routes);
+    }
+
+    /// Persist the analyser settings, the filters are retuned once the band edges change
+    fn analyser_change(&mut self) {
+        *self.params.analyser.write() = self.settings.analyser;
+        let analyser = &self.settings.analyser;
+        nih_trace!("Analyser Changed {:?}", analyser);
+        if analyser.enabled {
+            self.log.push(format!(
+                "Analyser on {} from {} to {} Hz, range {} dB",
+                analyser.target.name(),
+                analyser.low,
+                analyser.high,
+                analyser.range
+            ));
+        } else {
+            self.log.push(format!("Analyser off"));
+        }
+    }
+
+    fn address_schema_change(&mut self) {
+        *self.params.osc_address_schema.write() = self.settings.osc_address_schema.clone();
+        let schema = &self.settings.osc_address_schema;
+        nih_trace!("Address Schema Changed {:?}", schema);
+        self.log.push(format!(
+            "Address scheme: {}, {} custom template(s)",
+            schema.scheme.name(),
+            schema.templates.len()
+        ));
+        let schema = OscAddressSchemaType {
+            schema: schema.clone(),
+        };
+        let send_result = self
+            .sender
+            .send(OscChannelMessageType::AddressSchemaChange(schema.clone()));
+        if send_result.is_err() {
+            nih_error!("Failed to send AddressSchemaChange update {:?}", send_result.unwrap_err());
+            self.log.push(format!("Failed to update address scheme"));
+        }
+        let server_send_result = self
+            .server_sender
+            .send(OscServerMessageType::AddressSchemaChange(schema));
+        if server_send_result.is_err() {
+            nih_error!("Failed to send server AddressSchemaChange update {:?}", server_send_result.unwrap_err());
+            self.log.push(format!("Failed to update feedback address scheme"));
+        }
+    }
+
+    fn heartbeat_change(&mut self) {
+        *self.params.osc_heartbeat.write() = self.settings.osc_heartbeat.clone();
+        let heartbeat = &self.settings.osc_heartbeat;
+        nih_trace!("Heartbeat Changed {:?}", heartbeat);
+        if heartbeat.enabled {
+            self.log.push(format!(
+                "Heartbeat every {} s, timeout {} s",
+                heartbeat.interval, heartbeat.timeout
+            ));
+        } else {
+            self.log.push(format!("Heartbeat disabled"));
+        }
+        let send_result = self.sender.send(OscChannelMessageType::HeartbeatChange(
+            OscHeartbeatType {
+                heartbeat: self.settings.osc_heartbeat.clone(),
+            },
+        ));
+        if send_result.is_err() {
+            nih_error!("Failed to send HeartbeatChange update {:?}", send_result.unwrap_err());
+            self.log.push(format!("Failed to update heartbeat"));
+        }
+    }
+
+    /// Why an enabled destination can't be used, the first one found
+    fn destinations_error(&self) -> Option<String> {
+        let enabled = self.settings.osc_destinations.iter().filter(|destination| destination.enabled);
+        for (index, destination) in enabled.enumerate() {
+            if let HostInput::Invalid(error) = parse_host(&destination.address) {
+                return Some(format!("Destination {}: {}", index + 1, error));
+            }
+            if let Some(address_base) = &destination.address_base {
+                if !is_valid_address_base(address_base) {
+                    return Some(format!(
+                        "Destination {}: {} is not a valid base address, no spaces or # * , ? [ ] {{ }}",
+                        index + 1,
+                        address_base
+                    ));
+                }
+            }
+        }
+        None
+    }
+
+    /// Only a list the worker can use is persisted, like the main address base
+    fn persist_destinations(&mut self) {
+        if self.destinations_error().is_none() {
+            *self.params.osc_destinations.write() = self.settings.osc_destinations.clone();
+        }
+    }
+
+    /// Persist the destination list and hand it to the worker, unless an enabled one is invalid
+    fn destinations_change(&mut self) {
+        if let Some(error) = self.destinations_error() {
+            self.log.push(format!("Destinations not changed, {}", error));
+            return;
+        }
+        *self.params.osc_destinations.write() = self.settings.osc_destinations.clone();
+        let enabled = self
+            .settings
+            .osc_destinations
+            .iter()
+            .filter(|destination| destination.enabled)
+            .count();
+        self.log.push(format!(
+            "Sending to {} extra destination(s)",
+            enabled
+        ));
+        let send_result = self.sender.send(OscChannelMessageType::DestinationsChange(
+            OscDestinationsType {
+                destinations: self.settings.osc_destinations.clone(),
+            },
+        ));
+        if send_result.is_err() {
+            nih_error!("Failed to send DestinationsChange update {:?}", send_result.unwrap_err());
+            self.log.push(format!("Failed to update destinations"));
+        }
+    }
+}
+
 // Makes sense to also define this here, makes it a bit easier to keep track of
 pub(crate) fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (700, 400))
+    ViziaState::new(|| (700, 780))
 }
 
 pub(crate) fn create(
     params: Arc<OsClaPhotonParams>,
     sender: Arc<Sender<OscChannelMessageType>>,
+    server_sender: Arc<Sender<OscServerMessageType>>,
+    stats: Arc<OscStats>,
+    midi: Arc<MidiShared>,
+    gui_context: Arc<RwLock<Option<Arc<dyn GuiContext>>>>,
     editor_state: Arc<ViziaState>,
 ) -> Option<Box<dyn Editor>> {
-    create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
+    create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, context| {
+        //Hand the context to the feedback thread so incoming OSC can set params
+        *gui_context.write() = Some(context.clone());
+
         assets::register_noto_sans_light(cx);
         assets::register_noto_sans_thin(cx);
 
         OsClaPhotonEditor {
             sender: sender.clone(),
+            server_sender: server_sender.clone(),
             params: params.clone(),
+            stats: stats.clone(),
+            midi: midi.clone(),
             log: Vec::new(),
+            gui_context: context.clone(),
+            page: EditorPage::default(),
+            //Start with the first head so the group sliders do something right away
+            selected_heads: (0..NUM_PARAMS).map(|index| index == 0).collect(),
+            group_values: core::array::from_fn(|field| {
+                params.tilt_params[0].field(HeadField::ALL[field]).value()
+            }),
+            armed_variant: None,
             settings: OscSettings {
                 osc_server_address: params.osc_server_address.read().to_string(),
+                server_address_error: None,
                 osc_server_port: *params.osc_server_port.read(),
-                osc_address_base: params.osc_address_base.read().to_string()
+                osc_transport: *params.osc_transport.read(),
+                osc_tcp_framing: *params.osc_tcp_framing.read(),
+                osc_multicast: params.osc_multicast.read().clone(),
+                osc_address_base: params.osc_address_base.read().to_string(),
+                osc_address_schema: params.osc_address_schema.read().clone(),
+                selected_param: 0,
+                osc_head_output: *params.osc_head_output.read(),
+                osc_enum_args: params.osc_enum_args.read().clone(),
+                osc_mappings: params.osc_mappings.read().clone(),
+                osc_smoothings: params.osc_smoothings.read().clone(),
+                osc_output_rate: *params.osc_output_rate.read(),
+                osc_listen_port: *params.osc_listen_port.read(),
+                osc_heartbeat: params.osc_heartbeat.read().clone(),
+                osc_destinations: params.osc_destinations.read().clone(),
+                midi_bindings: params.midi_bindings.read().clone(),
+                midi_learning: false,
+                midi_outputs: params.midi_outputs.read().clone(),
+                note_mode: *params.note_mode.read(),
+                audio_follower: *params.audio_follower.read(),
+                follower_routes: params.follower_routes.read().clone(),
+                analyser: *params.analyser.read(),
             }.into()
         }
         .build(cx);
 
+        //Stops once the editor is closed and the event can't be delivered any more
+        cx.spawn(|cx| loop {
+            thread::sleep(REFRESH_INTERVAL);
+            if cx.emit(OsClaPhotonEditorEvent::Refresh).is_err() {
+                break;
+            }
+        });
+
         //Uncomment for debugging styles
         //cx.add_stylesheet(include_style!("src/style.css")).expect("Failed to load stylesheet");
 
@@ -124,9 +1371,40 @@ pub(crate) fn create(
                 .left(Units::Pixels(5.0))
                 .class("title");
             HStack::new(cx, |cx| {
-                SettingsView::new(cx, OsClaPhotonEditor::settings, OsClaPhotonEditor::params, OsClaPhotonEditor::log);
-                ParamView::new(cx, OsClaPhotonEditor::params);
-            });
+                for page in [EditorPage::Settings, EditorPage::Heads] {
+                    Button::new(
+                        cx,
+                        move |cx| cx.emit(OsClaPhotonEditorEvent::ShowPage(page)),
+                        move |cx| Label::new(cx, page.name()),
+                    )
+                    .toggle_class("selected", OsClaPhotonEditor::page.map(move |shown| *shown == page))
+                    .width(Pixels(95.0));
+                }
+            })
+            .class("row");
+            //The settings are a long column, the page scrolls instead of the window growing with it
+            ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+                Binding::new(cx, OsClaPhotonEditor::page.map(|page| *page == EditorPage::Heads), |cx, heads| {
+                    if heads.get(cx) {
+                        HeadView::new(
+                            cx,
+                            OsClaPhotonEditor::params,
+                            OsClaPhotonEditor::settings,
+                            OsClaPhotonEditor::selected_heads,
+                            OsClaPhotonEditor::group_values,
+                        );
+                    } else {
+                        HStack::new(cx, |cx| {
+                            VStack::new(cx, |cx| {
+                                SettingsView::new(cx, OsClaPhotonEditor::settings, OsClaPhotonEditor::params, OsClaPhotonEditor::log);
+                                StatusView::new(cx, OsClaPhotonEditor::stats);
+                            });
+                            ParamView::new(cx, OsClaPhotonEditor::params, OsClaPhotonEditor::armed_variant);
+                        });
+                    }
+                });
+            })
+            .height(Stretch(1.0));
         });
     })
 }
@@ -0,0 +1,243 @@
+use rosc::{OscColor, OscMessage, OscType};
+use serde::{Deserialize, Serialize};
+
+use crate::address::{HeadField, ParamId};
+use crate::enum_variant_ids;
+use crate::mapping::OscMappings;
+
+// /<osc_address_base>/head/<n> <tilt> <dimmer> <red> <green> <blue>
+// /<osc_address_base>/head/<n> <tilt> <dimmer> <rgba>
+// <param_address>/<variant_id> 1, enum triggers
+
+/// How an enum param's value goes out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum EnumArg {
+    /// The variant index as a float, breaks if the enum is reordered
+    #[default]
+    Float,
+    /// The variant index as an int
+    Int,
+    /// The variant `#[id]` as a string
+    String,
+    /// A message to `<param_address>/<variant_id>` with a 1, one address per variant
+    Trigger,
+}
+
+impl EnumArg {
+    pub fn name(self) -> &'static str {
+        match self {
+            EnumArg::Float => "Float",
+            EnumArg::Int => "Int",
+            EnumArg::String => "String",
+            EnumArg::Trigger => "Trigger",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            EnumArg::Float => EnumArg::Int,
+            EnumArg::Int => EnumArg::String,
+            EnumArg::String => EnumArg::Trigger,
+            EnumArg::Trigger => EnumArg::Float,
+        }
+    }
+}
+
+/// How each enum param goes out, one setting per param
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OscEnumArgs {
+    pub control: EnumArg,
+    pub shutter: EnumArg,
+    pub rotation: EnumArg,
+}
+
+impl OscEnumArgs {
+    /// Float params always go out as floats
+    pub fn get(&self, id: ParamId) -> EnumArg {
+        match id {
+            ParamId::Control => self.control,
+            ParamId::Shutter => self.shutter,
+            ParamId::Rotation => self.rotation,
+            _ => EnumArg::Float,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: ParamId) -> Option<&mut EnumArg> {
+        match id {
+            ParamId::Control => Some(&mut self.control),
+            ParamId::Shutter => Some(&mut self.shutter),
+            ParamId::Rotation => Some(&mut self.rotation),
+            _ => None,
+        }
+    }
+}
+
+/// The message for one param at its schema address
+pub fn param_message(
+    address: &str,
+    id: ParamId,
+    value: f32,
+    enum_args: &OscEnumArgs,
+    mappings: &OscMappings,
+) -> OscMessage {
+    let variant = || {
+        enum_variant_ids(id)
+            .get(value.max(0.0) as usize)
+            .copied()
+            .unwrap_or_default()
+    };
+    match enum_args.get(id) {
+        //Float params always end up here, enums never have a mapping
+        EnumArg::Float => OscMessage {
+            addr: address.to_string(),
+            args: vec![mappings.arg(id, value)],
+        },
+        EnumArg::Int => OscMessage {
+            addr: address.to_string(),
+            args: vec![OscType::Int(value as i32)],
+        },
+        EnumArg::String => OscMessage {
+            addr: address.to_string(),
+            args: vec![OscType::String(variant().to_string())],
+        },
+        EnumArg::Trigger => OscMessage {
+            addr: format!("{}/{}", address, variant()),
+            args: vec![OscType::Int(1)],
+        },
+    }
+}
+
+/// Variant index for a variant `#[id]`, from a string arg or a trigger address
+pub fn parse_enum_variant(id: ParamId, variant: &str) -> Option<f32> {
+    enum_variant_ids(id)
+        .iter()
+        .position(|variant_id| *variant_id == variant)
+        .map(|index| index as f32)
+}
+
+/// How the head params go out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum HeadOutput {
+    /// One message per field, at the address from the schema
+    #[default]
+    Separate,
+    /// One message per changed head with all five fields, so the colour never shows half updated
+    Grouped,
+    /// Like `Grouped` with red, green and blue packed into one RGBA colour
+    GroupedColor,
+}
+
+impl HeadOutput {
+    pub fn name(self) -> &'static str {
+        match self {
+            HeadOutput::Separate => "Separate",
+            HeadOutput::Grouped => "Grouped",
+            HeadOutput::GroupedColor => "Grouped RGBA",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            HeadOutput::Separate => HeadOutput::Grouped,
+            HeadOutput::Grouped => HeadOutput::GroupedColor,
+            HeadOutput::GroupedColor => HeadOutput::Separate,
+        }
+    }
+}
+
+pub fn head_address(address_base: &str, index: usize) -> String {
+    format!("{}/head/{}", address_base, index + 1)
+}
+
+/// All fields of one head, `values` is indexed by `ParamId::index()`. The RGBA colour always
+/// uses the unmapped values
+pub fn head_message(
+    address_base: &str,
+    index: usize,
+    values: &[f32],
+    head_output: HeadOutput,
+    mappings: &OscMappings,
+) -> OscMessage {
+    let value = |field| {
+        values
+            .get(ParamId::Head(index, field).index())
+            .copied()
+            .unwrap_or(0.0)
+    };
+    let arg = |field| mappings.arg(ParamId::Head(index, field), value(field));
+    let mut args = vec![arg(HeadField::Tilt), arg(HeadField::Dimmer)];
+    if head_output == HeadOutput::GroupedColor {
+        args.push(OscType::Color(OscColor {
+            red: color_byte(value(HeadField::Red)),
+            green: color_byte(value(HeadField::Green)),
+            blue: color_byte(value(HeadField::Blue)),
+            alpha: u8::MAX,
+        }));
+    } else {
+        args.push(arg(HeadField::Red));
+        args.push(arg(HeadField::Green));
+        args.push(arg(HeadField::Blue));
+    }
+    OscMessage {
+        addr: head_address(address_base, index),
+        args,
+    }
+}
+
+/// Read a grouped head message back into its fields, either layout is accepted. Float args are
+/// unmapped, colours are taken as is
+pub fn parse_head_message(
+    message: &OscMessage,
+    address_base: &str,
+    mappings: &OscMappings,
+) -> Option<(usize, Vec<(HeadField, f32)>)> {
+    let index = message
+        .addr
+        .strip_prefix(address_base)?
+        .strip_prefix("/head/")?
+        .parse::<usize>()
+        .ok()?
+        .checked_sub(1)?;
+    if index >= crate::NUM_PARAMS {
+        return None;
+    }
+
+    let mut values = Vec::with_capacity(HeadField::ALL.len());
+    let unmap = |position: usize, value: f32| match HeadField::ALL.get(position) {
+        Some(field) => mappings.unmap(ParamId::Head(index, *field), value),
+        None => value,
+    };
+    for arg in &message.args {
+        match arg {
+            OscType::Float(value) => values.push(unmap(values.len(), *value)),
+            OscType::Double(value) => values.push(unmap(values.len(), *value as f32)),
+            OscType::Int(value) => values.push(unmap(values.len(), *value as f32)),
+            OscType::Color(color) => {
+                values.push(color.red as f32 / 255.0);
+                values.push(color.green as f32 / 255.0);
+                values.push(color.blue as f32 / 255.0);
+            }
+            _ => return None,
+        }
+    }
+    if values.len() != HeadField::ALL.len() {
+        return None;
+    }
+    Some((index, HeadField::ALL.into_iter().zip(values).collect()))
+}
+
+/// What feedback reads back when a receiver echoes the value we sent for a param unchanged
+pub fn echo_value(id: ParamId, value: f32, head_output: HeadOutput, mappings: &OscMappings) -> f32 {
+    match id {
+        ParamId::Head(_, HeadField::Red | HeadField::Green | HeadField::Blue)
+            if head_output == HeadOutput::GroupedColor =>
+        {
+            color_byte(value) as f32 / 255.0
+        }
+        _ => mappings.echo(id, value),
+    }
+}
+
+fn color_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
@@ -0,0 +1,384 @@
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use nih_plug::debug::*;
+use nih_plug::prelude::{GuiContext, ParamSetter};
+use parking_lot::RwLock;
+use rosc::{OscMessage, OscPacket, OscType};
+use std::io::ErrorKind;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::address::{AddressTable, OscAddressSchema, ParamId};
+use crate::encoding::{parse_enum_variant, parse_head_message};
+use crate::mapping::OscMappings;
+use crate::heartbeat::heartbeat_ack_base;
+use crate::stats::OscStats;
+use crate::transport::{join_multicast, OscTransport};
+use crate::{
+    format_osc_address_base, OsClaPhotonParams, OscChannelMessageType, OscServerMessageType,
+    OscTransportType,
+};
+
+/// How long a read blocks before we check the channel again
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+// <param_address> <param_value>, param_address as set by the address schema
+// <param_address> <variant_id>, enums only
+// <param_address>/<variant_id>, enum triggers
+// /<osc_address_base>/head/<n> <tilt> <dimmer> <red> <green> <blue>, or an rgba colour
+// /<osc_address_base>/heartbeat/ack
+// /<osc_address_base>/dump
+
+/// Marks a slot nothing was sent for yet
+const NOTHING_SENT: u32 = f32::NAN.to_bits();
+
+/// Frames remembered per ramping param. A smoothed param sends a frame every tick, so the echo of
+/// a frame usually arrives after the next ones went out
+const ECHO_HISTORY: usize = 16;
+
+/// What the worker sent per param, in the form feedback reads them back. A receiver that echoes
+/// everything would otherwise write our own values into the params, mapped and quantized, or stall
+/// a ramp by writing its earlier frames back. Only the last value counts, plus the frames of a
+/// ramp while it runs, so an operator can still go back to any earlier value
+pub(crate) struct SentValues {
+    last: Vec<AtomicU32>,
+    //Frames of the running ramp, all NOTHING_SENT while the param isn't ramping
+    ramps: Vec<[AtomicU32; ECHO_HISTORY]>,
+    ramping: Vec<AtomicBool>,
+    //Slot the next frame of each param goes to, only the worker writes
+    next: Vec<AtomicUsize>,
+}
+
+impl Default for SentValues {
+    fn default() -> Self {
+        Self {
+            last: ParamId::all().map(|_| AtomicU32::new(NOTHING_SENT)).collect(),
+            ramps: ParamId::all()
+                .map(|_| core::array::from_fn(|_| AtomicU32::new(NOTHING_SENT)))
+                .collect(),
+            ramping: ParamId::all().map(|_| AtomicBool::new(false)).collect(),
+            next: ParamId::all().map(|_| AtomicUsize::new(0)).collect(),
+        }
+    }
+}
+
+impl SentValues {
+    /// `echo` as returned by `echo_value()`
+    pub fn record(&self, id: ParamId, echo: f32) {
+        let index = id.index();
+        if let Some(last) = self.last.get(index) {
+            last.store(echo.to_bits(), Ordering::Relaxed);
+        }
+        if !self.ramping.get(index).is_some_and(|ramping| ramping.load(Ordering::Relaxed)) {
+            return;
+        }
+        if let (Some(frames), Some(next)) = (self.ramps.get(index), self.next.get(index)) {
+            let slot = next.fetch_add(1, Ordering::Relaxed) % ECHO_HISTORY;
+            frames[slot].store(echo.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// A smoothed param got a new target, frames of an earlier ramp no longer count
+    pub fn start_ramp(&self, id: ParamId) {
+        self.clear_ramp(id);
+        if let Some(ramping) = self.ramping.get(id.index()) {
+            ramping.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The param reached its target or jumped, only its last value counts from now on
+    pub fn end_ramp(&self, id: ParamId) {
+        if let Some(ramping) = self.ramping.get(id.index()) {
+            ramping.store(false, Ordering::Relaxed);
+        }
+        self.clear_ramp(id);
+    }
+
+    fn clear_ramp(&self, id: ParamId) {
+        if let (Some(frames), Some(next)) = (self.ramps.get(id.index()), self.next.get(id.index())) {
+            for frame in frames {
+                frame.store(NOTHING_SENT, Ordering::Relaxed);
+            }
+            next.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_echo(&self, id: ParamId, value: f32) -> bool {
+        let matches = |sent: &AtomicU32| f32::from_bits(sent.load(Ordering::Relaxed)) == value;
+        self.last.get(id.index()).is_some_and(matches)
+            || self
+                .ramps
+                .get(id.index())
+                .is_some_and(|frames| frames.iter().any(matches))
+    }
+}
+
+/// What the server thread shares with the rest of the plugin
+pub(crate) struct FeedbackHandles {
+    pub params: Arc<OsClaPhotonParams>,
+    pub stats: Arc<OscStats>,
+    pub sent: Arc<SentValues>,
+    //Lets a dump query reach the OSC client worker
+    pub client_sender: Arc<Sender<OscChannelMessageType>>,
+    pub gui_context: Arc<RwLock<Option<Arc<dyn GuiContext>>>>,
+}
+
+/// Listens for OSC sent back from TD-Photon and applies it to the plugin params.
+///
+/// Changes go through the editor's `GuiContext`, the same way a slider drag does, so the host
+/// sees them and can record them as automation. Until the editor has been opened once there is
+/// no context yet, then incoming values are played as overrides like the MIDI controls until the
+/// host moves the param.
+pub(crate) fn osc_server_worker(
+    listen_port: Option<u16>,
+    transport: OscTransportType,
+    param_address_base: String,
+    schema: OscAddressSchema,
+    mappings: OscMappings,
+    handles: FeedbackHandles,
+    recv: Receiver<OscServerMessageType>,
+) {
+    nih_trace!("Server thread spawned!");
+    let mut address_base = format_osc_address_base(&param_address_base);
+    let mut schema = schema;
+    let mut addresses = AddressTable::new(&schema, &address_base);
+    let mut mappings = mappings;
+    let mut listen_port = listen_port;
+    let mut transport = transport;
+    let mut socket = listen_port.and_then(|port| bind_listen_socket(port, &transport));
+    handles.stats.set_listening(socket.is_some());
+    let mut buf = [0u8; rosc::decoder::MTU];
+    loop {
+        //Without a socket there is nothing to poll so just wait on the channel
+        let channel_message = match socket {
+            Some(_) => match recv.try_recv() {
+                Ok(channel_message) => Some(channel_message),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            },
+            None => match recv.recv() {
+                Ok(channel_message) => Some(channel_message),
+                Err(_) => break,
+            },
+        };
+        if let Some(channel_message) = channel_message {
+            match channel_message {
+                OscServerMessageType::Exit => break,
+                OscServerMessageType::ListenPortChange(message) => {
+                    nih_trace!("Listen Port Change: {:?}", message.port);
+                    //Drop the old socket first so we can rebind the same port
+                    socket.take();
+                    listen_port = message.port;
+                    socket = listen_port.and_then(|port| bind_listen_socket(port, &transport));
+                }
+                OscServerMessageType::TransportChange(message) => {
+                    transport = message;
+                    //Rebind so we leave the old group and join the new one
+                    socket.take();
+                    socket = listen_port.and_then(|port| bind_listen_socket(port, &transport));
+                }
+                OscServerMessageType::AddressBaseChange(message) => {
+                    address_base = format_osc_address_base(&message.address);
+                    nih_trace!("Server AddressBase Change: {}", address_base);
+                    addresses = AddressTable::new(&schema, &address_base);
+                }
+                OscServerMessageType::AddressSchemaChange(message) => {
+                    schema = message.schema;
+                    addresses = AddressTable::new(&schema, &address_base);
+                }
+                OscServerMessageType::MappingsChange(message) => mappings = message.mappings,
+            }
+            handles.stats.set_listening(socket.is_some());
+            continue;
+        }
+
+        if let Some(socket) = &socket {
+            match socket.recv_from(&mut buf) {
+                Ok((size, _)) => match rosc::decoder::decode_udp(&buf[..size]) {
+                    Ok((_, packet)) => {
+                        let context = FeedbackContext {
+                            address_base: &address_base,
+                            addresses: &addresses,
+                            mappings: &mappings,
+                            handles: &handles,
+                        };
+                        handle_packet(&packet, &context)
+                    }
+                    Err(e) => nih_error!("Failed to decode osc packet {:?}", e),
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+                Err(e) => nih_error!("Failed to receive osc packet {:?}", e),
+            }
+        }
+    }
+    handles.stats.set_listening(false);
+}
+
+fn bind_listen_socket(port: u16, transport: &OscTransportType) -> Option<UdpSocket> {
+    let socket = match UdpSocket::bind(("0.0.0.0", port)) {
+        Ok(socket) => socket,
+        Err(e) => {
+            nih_error!("Failed to bind listen socket on port {} {:?}", port, e);
+            return None;
+        }
+    };
+    if let Err(e) = socket.set_read_timeout(Some(READ_TIMEOUT)) {
+        nih_error!("Failed to set listen socket timeout {:?}", e);
+        return None;
+    }
+    if transport.transport == OscTransport::UdpMulticast {
+        join_multicast(&socket, &transport.multicast);
+    }
+    nih_trace!("Listening on port {}", port);
+    Some(socket)
+}
+
+/// Everything an incoming message might need to touch
+struct FeedbackContext<'a> {
+    address_base: &'a str,
+    addresses: &'a AddressTable,
+    mappings: &'a OscMappings,
+    handles: &'a FeedbackHandles,
+}
+
+fn handle_packet(packet: &OscPacket, context: &FeedbackContext) {
+    match packet {
+        OscPacket::Message(message) => handle_message(message, context),
+        //Feedback is applied as soon as it arrives, timetags are ignored
+        OscPacket::Bundle(bundle) => {
+            for packet in &bundle.content {
+                handle_packet(packet, context);
+            }
+        }
+    }
+}
+
+fn handle_message(message: &OscMessage, context: &FeedbackContext) {
+    if let Some(address_base) = heartbeat_ack_base(&message.addr) {
+        context.handles.stats.record_ack(address_base);
+        return;
+    }
+    if message.addr.strip_prefix(context.address_base) == Some("/dump") {
+        nih_trace!("Dump requested");
+        let send_result = context.handles.client_sender.send(OscChannelMessageType::SendAll);
+        if send_result.is_err() {
+            nih_error!("Failed to send SendAll {:?}", send_result.unwrap_err());
+        }
+        return;
+    }
+    if let Some((index, fields)) = parse_head_message(message, context.address_base, context.mappings) {
+        for (field, value) in fields {
+            apply_value(ParamId::Head(index, field), value, context);
+        }
+        return;
+    }
+    if let Some((id, value)) = find_enum_trigger(&message.addr, context.addresses) {
+        apply_value(id, value, context);
+        return;
+    }
+    let id = match context.addresses.find(&message.addr) {
+        Some(id) => id,
+        None => {
+            nih_trace!("Unknown address in feedback: {}", message.addr);
+            context.handles.stats.record_feedback(false);
+            return;
+        }
+    };
+    let value = match message.args.first() {
+        Some(OscType::Float(value)) => *value,
+        Some(OscType::Double(value)) => *value as f32,
+        Some(OscType::Int(value)) => *value as f32,
+        Some(OscType::Bool(value)) => *value as u8 as f32,
+        Some(OscType::String(variant)) => match parse_enum_variant(id, variant) {
+            Some(value) => value,
+            None => {
+                nih_trace!("Unknown variant {} for {}", variant, id.name());
+                context.handles.stats.record_feedback(false);
+                return;
+            }
+        },
+        _ => {
+            nih_trace!("Unsupported arguments in feedback: {:?}", message.args);
+            context.handles.stats.record_feedback(false);
+            return;
+        }
+    };
+    apply_value(id, context.mappings.unmap(id, value), context);
+}
+
+/// `<param_address>/<variant_id>` for an enum param
+fn find_enum_trigger(address: &str, addresses: &AddressTable) -> Option<(ParamId, f32)> {
+    let (param_address, variant) = address.rsplit_once('/')?;
+    let id = addresses.find(param_address)?;
+    parse_enum_variant(id, variant).map(|value| (id, value))
+}
+
+fn apply_value(id: ParamId, value: f32, context: &FeedbackContext) {
+    if context.handles.sent.is_echo(id, value) {
+        nih_trace!("Ignoring echo of {} {}", id.name(), value);
+        return;
+    }
+    let params = &context.handles.params;
+    let applied = match &*context.handles.gui_context.read() {
+        Some(gui_context) => {
+            params.set_from_osc(Some(&ParamSetter::new(gui_context.as_ref())), id, value)
+        }
+        None => params.set_from_osc(None, id, value),
+    };
+    context.handles.stats.record_feedback(applied);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enum_goes_back_to_an_earlier_variant() {
+        let sent = SentValues::default();
+        for variant in [0.0, 1.0, 2.0] {
+            sent.record(ParamId::Control, variant);
+        }
+        assert!(sent.is_echo(ParamId::Control, 2.0));
+        assert!(!sent.is_echo(ParamId::Control, 0.0));
+        assert!(!sent.is_echo(ParamId::Control, 1.0));
+    }
+
+    #[test]
+    fn unsmoothed_float_only_matches_the_last_value() {
+        let sent = SentValues::default();
+        sent.record(ParamId::Zoom, 0.25);
+        sent.record(ParamId::Zoom, 0.5);
+        assert!(sent.is_echo(ParamId::Zoom, 0.5));
+        assert!(!sent.is_echo(ParamId::Zoom, 0.25));
+    }
+
+    #[test]
+    fn ramp_frames_count_until_the_ramp_ends() {
+        let sent = SentValues::default();
+        sent.start_ramp(ParamId::Zoom);
+        for frame in [0.1, 0.2, 0.3] {
+            sent.record(ParamId::Zoom, frame);
+        }
+        assert!(sent.is_echo(ParamId::Zoom, 0.1));
+        assert!(sent.is_echo(ParamId::Zoom, 0.3));
+
+        sent.end_ramp(ParamId::Zoom);
+        sent.record(ParamId::Zoom, 0.4);
+        assert!(sent.is_echo(ParamId::Zoom, 0.4));
+        assert!(!sent.is_echo(ParamId::Zoom, 0.1));
+        assert!(!sent.is_echo(ParamId::Zoom, 0.3));
+    }
+
+    #[test]
+    fn retarget_drops_the_earlier_ramp() {
+        let sent = SentValues::default();
+        sent.start_ramp(ParamId::Zoom);
+        sent.record(ParamId::Zoom, 0.1);
+        sent.record(ParamId::Zoom, 0.2);
+        sent.start_ramp(ParamId::Zoom);
+        sent.record(ParamId::Zoom, 0.15);
+        assert!(!sent.is_echo(ParamId::Zoom, 0.1));
+        assert!(sent.is_echo(ParamId::Zoom, 0.15));
+    }
+}
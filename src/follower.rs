@@ -0,0 +1,182 @@
+use nih_plug::prelude::Buffer;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::address::ParamId;
+
+/// Level the threshold can be set down to, and what silence reads as
+const FLOOR_DB: f32 = -96.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum FollowerMode {
+    /// Follows the loudness, smoother
+    #[default]
+    Rms,
+    /// Follows the highest sample, snappier on drums
+    Peak,
+}
+
+impl FollowerMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            FollowerMode::Rms => "RMS",
+            FollowerMode::Peak => "Peak",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            FollowerMode::Rms => FollowerMode::Peak,
+            FollowerMode::Peak => FollowerMode::Rms,
+        }
+    }
+}
+
+/// How the input level becomes the 0-1 follower output, the params it drives are in
+/// `FollowerRoutes`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FollowerSettings {
+    pub enabled: bool,
+    pub mode: FollowerMode,
+    /// Seconds to rise by about two thirds
+    pub attack: f32,
+    /// Seconds to fall by about two thirds
+    pub release: f32,
+    /// dB added to the input before the threshold
+    pub gain: f32,
+    /// dB where the output starts to rise, it reaches 1 at 0 dBFS
+    pub threshold: f32,
+}
+
+impl Default for FollowerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: FollowerMode::Rms,
+            attack: 0.01,
+            release: 0.2,
+            gain: 0.0,
+            threshold: -40.0,
+        }
+    }
+}
+
+/// How much of the follower output is added to a param, `invert` subtracts it instead
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FollowerRoute {
+    /// 0-1 param units at full output
+    pub depth: f32,
+    pub invert: bool,
+}
+
+/// Follower routes by host param id, enums can't be routed
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FollowerRoutes {
+    pub params: HashMap<String, FollowerRoute>,
+}
+
+/// Runs in process(), one value for both channels
+#[derive(Default)]
+pub struct EnvelopeFollower {
+    envelope: f32,
+}
+
+impl EnvelopeFollower {
+    /// Follows the block and returns the output, 0-1 after gain and threshold
+    pub fn process(&mut self, settings: &FollowerSettings, sample_rate: f32, buffer: &mut Buffer) -> f32 {
+        if !settings.enabled {
+            self.envelope = 0.0;
+            return 0.0;
+        }
+        let coefficient = |seconds: f32| {
+            if seconds > 0.0 {
+                (-1.0 / (seconds * sample_rate)).exp()
+            } else {
+                0.0
+            }
+        };
+        let attack = coefficient(settings.attack);
+        let release = coefficient(settings.release);
+        for frame in buffer.iter_samples() {
+            //Squares for RMS so the root is taken once at the end
+            let (mut input, mut channels) = (0.0f32, 0);
+            for sample in frame {
+                match settings.mode {
+                    FollowerMode::Rms => input += *sample * *sample,
+                    FollowerMode::Peak => input = input.max(sample.abs()),
+                }
+                channels += 1;
+            }
+            if settings.mode == FollowerMode::Rms && channels > 0 {
+                input /= channels as f32;
+            }
+            let coefficient = if input > self.envelope { attack } else { release };
+            self.envelope = input + coefficient * (self.envelope - input);
+        }
+        let level = match settings.mode {
+            FollowerMode::Rms => self.envelope.sqrt(),
+            FollowerMode::Peak => self.envelope,
+        };
+        let level_db = if level > 0.0 { 20.0 * level.log10() } else { FLOOR_DB };
+        let threshold = settings.threshold.clamp(FLOOR_DB, -0.1);
+        ((level_db + settings.gain - threshold) / -threshold).clamp(0.0, 1.0)
+    }
+}
+
+/// What the follower adds to each param right now, the routed depth times the last output.
+/// `apply()` updates the offsets once per block
+pub struct ParamModulation {
+    /// Signed depth per param in `ParamId::all()` order, 0 when not routed
+    depths: Vec<AtomicU32>,
+    offsets: Vec<AtomicU32>,
+    /// Last follower output for the editor
+    level: AtomicU32,
+}
+
+impl Default for ParamModulation {
+    fn default() -> Self {
+        Self {
+            depths: ParamId::all().map(|_| AtomicU32::new(0)).collect(),
+            offsets: ParamId::all().map(|_| AtomicU32::new(0)).collect(),
+            level: AtomicU32::new(0),
+        }
+    }
+}
+
+impl ParamModulation {
+    pub fn set_routes(&self, routes: &FollowerRoutes) {
+        for (depth, id) in self.depths.iter().zip(ParamId::all()) {
+            let route = routes.params.get(&id.host_id()).filter(|_| !id.is_enum());
+            let value = route.map_or(0.0, |route| {
+                if route.invert {
+                    -route.depth
+                } else {
+                    route.depth
+                }
+            });
+            depth.store(value.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn offset(&self, id: ParamId) -> f32 {
+        self.offsets
+            .get(id.index())
+            .map_or(0.0, |offset| f32::from_bits(offset.load(Ordering::Relaxed)))
+    }
+
+    pub fn level(&self) -> f32 {
+        f32::from_bits(self.level.load(Ordering::Relaxed))
+    }
+
+    /// Safe to call from process(). Calls `on_change` for every param whose offset moved
+    pub fn apply(&self, level: f32, mut on_change: impl FnMut(ParamId)) {
+        self.level.store(level.to_bits(), Ordering::Relaxed);
+        for ((depth, offset), id) in self.depths.iter().zip(&self.offsets).zip(ParamId::all()) {
+            let value = f32::from_bits(depth.load(Ordering::Relaxed)) * level;
+            if offset.swap(value.to_bits(), Ordering::Relaxed) != value.to_bits() {
+                on_change(id);
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use rosc::{OscMessage, OscType};
+use serde::{Deserialize, Serialize};
+
+// /<osc_address_base>/heartbeat <counter> <plugin_version>
+// /<osc_address_base>/heartbeat/ack
+
+/// Shortest interval and timeout, and the shortest resync besides 0
+pub const MIN_SECONDS: f32 = 0.1;
+/// Longest interval, timeout and resync, anything above is most likely a typo
+pub const MAX_SECONDS: f32 = 3600.0;
+
+/// Whether a heartbeat time in seconds is in range, "inf" and "NaN" parse as floats too
+pub fn is_valid_seconds(seconds: f32) -> bool {
+    (MIN_SECONDS..=MAX_SECONDS).contains(&seconds)
+}
+
+/// How often we ping the receiver and how long it may stay quiet
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OscHeartbeat {
+    pub enabled: bool,
+    /// Seconds between heartbeats
+    pub interval: f32,
+    /// Seconds without an ack before the peer counts as offline
+    pub timeout: f32,
+    /// Seconds without any param change before the full state is resent, 0 turns it off
+    pub resync_after: f32,
+}
+
+impl Default for OscHeartbeat {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: 1.0,
+            timeout: 3.0,
+            resync_after: 10.0,
+        }
+    }
+}
+
+impl OscHeartbeat {
+    /// Every time in range, whatever was persisted. The worker builds `Duration`s from these
+    pub fn clamped(&self) -> Self {
+        let clamp = |seconds: f32, default: f32| {
+            if seconds.is_nan() {
+                default
+            } else {
+                seconds.clamp(MIN_SECONDS, MAX_SECONDS)
+            }
+        };
+        let defaults = Self::default();
+        Self {
+            enabled: self.enabled,
+            interval: clamp(self.interval, defaults.interval),
+            timeout: clamp(self.timeout, defaults.timeout),
+            resync_after: if self.resync_after == 0.0 {
+                0.0
+            } else {
+                clamp(self.resync_after, defaults.resync_after)
+            },
+        }
+    }
+}
+
+pub fn heartbeat_message(address_base: &str, counter: i32) -> OscMessage {
+    OscMessage {
+        addr: format!("{}/heartbeat", address_base),
+        args: vec![
+            OscType::Int(counter),
+            OscType::String(env!("CARGO_PKG_VERSION").to_string()),
+        ],
+    }
+}
+
+/// The address base an ack was sent for, the worker matches it against each destination's base
+pub fn heartbeat_ack_base(address: &str) -> Option<&str> {
+    address.strip_suffix("/heartbeat/ack")
+}
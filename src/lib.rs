@@ -1,32 +1,108 @@
-use anyhow::Result;
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use nih_plug::debug::*;
 use nih_plug::prelude::*;
 use nih_plug_vizia::ViziaState;
 use parking_lot::RwLock;
-use rosc::{OscMessage, OscPacket, OscType};
-use std::net::UdpSocket;
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime};
+use serde::{Deserialize, Serialize};
+use std::net::ToSocketAddrs;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
+use std::collections::VecDeque;
 use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
-use array_const_fn_init::array_const_fn_init;
+use std::time::Duration;
 
+mod address;
+mod analysis;
 mod editor;
+mod encoding;
+mod feedback;
+mod follower;
+mod heartbeat;
+mod mapping;
+mod midi;
+mod notes;
+mod overrides;
+mod scheduler;
+mod smoothing;
+mod stats;
 mod subviews;
-
-const NUM_PARAMS:usize = 8;
-const fn const_add_one(i: usize) -> usize {
-    i + 1
-}
-const INDEXES: [usize; NUM_PARAMS] = array_const_fn_init![const_add_one; 8];
+mod timing;
+mod transport;
+
+use crate::address::{is_valid_address_base, AddressTable, HeadField, OscAddressSchema, ParamId};
+use crate::encoding::{echo_value, head_message, param_message, HeadOutput, OscEnumArgs};
+use crate::feedback::SentValues;
+use crate::analysis::{AnalyserSettings, Filterbank};
+use crate::follower::{EnvelopeFollower, FollowerRoutes, FollowerSettings, ParamModulation};
+use crate::heartbeat::{heartbeat_message, OscHeartbeat};
+use crate::mapping::OscMappings;
+use crate::midi::{MidiBindings, MidiDecoder, MidiOutputs, MidiShared};
+use crate::notes::{HeadOverrides, NoteMode, NotePlayer};
+use crate::overrides::ParamOverrides;
+use crate::scheduler::{OutputRate, ParamSnapshot};
+use crate::smoothing::{OscSmoothings, Smoothers, SMOOTHING_FRAME_RATE};
+use crate::stats::{OscStats, PeerState, PeerStatus};
+use crate::timing::{ntp_from_unix_secs, unix_now_secs, SampleClock, OSC_TIME_IMMEDIATELY};
+use crate::transport::{OscLink, OscMulticast, OscTransport, TcpFraming, UdpSockets};
+
+//Number of tilt heads, picked at build time with one of the heads-4, heads-16 or heads-32
+//features and 8 otherwise. Each count is its own plugin with its own CLAP id so a project can use
+//several side by side, the 8 head build keeps the original id. The features are additive so
+//--all-features still builds, the largest enabled count wins
+#[cfg(all(feature = "heads-4", not(any(feature = "heads-16", feature = "heads-32"))))]
+const NUM_PARAMS: usize = 4;
+#[cfg(all(feature = "heads-16", not(feature = "heads-32")))]
+const NUM_PARAMS: usize = 16;
+#[cfg(feature = "heads-32")]
+const NUM_PARAMS: usize = 32;
+#[cfg(not(any(feature = "heads-4", feature = "heads-16", feature = "heads-32")))]
+const NUM_PARAMS: usize = 8;
+
+#[cfg(all(feature = "heads-4", not(any(feature = "heads-16", feature = "heads-32"))))]
+const PLUGIN_VARIANT: (&str, &str) = ("OSCLAPHOTON 4", "xyz.vanta.osclaphoton.4heads");
+#[cfg(all(feature = "heads-16", not(feature = "heads-32")))]
+const PLUGIN_VARIANT: (&str, &str) = ("OSCLAPHOTON 16", "xyz.vanta.osclaphoton.16heads");
+#[cfg(feature = "heads-32")]
+const PLUGIN_VARIANT: (&str, &str) = ("OSCLAPHOTON 32", "xyz.vanta.osclaphoton.32heads");
+#[cfg(not(any(feature = "heads-4", feature = "heads-16", feature = "heads-32")))]
+const PLUGIN_VARIANT: (&str, &str) = ("OSCLAPHOTON", "xyz.vanta.osclaphoton");
+
+const OSC_CHANNEL_CAPACITY: usize = 65_536;
+//Seconds between connect checks while a TCP link that missed something is down
+const TCP_POLL_INTERVAL: f64 = 0.05;
+//Enum changes kept per TCP link while it is down
+const TCP_MAX_MISSED_CUES: usize = 256;
 
 pub struct OsClaPhoton {
     params: Arc<OsClaPhotonParams>,
     osc_thread: Option<JoinHandle<()>>,
     sender: Arc<Sender<OscChannelMessageType>>,
     receiver: Option<Receiver<OscChannelMessageType>>,
+    server_thread: Option<JoinHandle<()>>,
+    server_sender: Arc<Sender<OscServerMessageType>>,
+    server_receiver: Option<Receiver<OscServerMessageType>>,
+    //Bindings and learn state shared with the editor
+    midi: Arc<MidiShared>,
+    midi_decoder: MidiDecoder,
+    //Copy of the persisted note mode so process() never waits on the editor
+    note_mode: NoteMode,
+    note_player: NotePlayer,
+    //Copy of the persisted follower settings, same as the note mode
+    follower_settings: FollowerSettings,
+    follower: EnvelopeFollower,
+    //Copy of the persisted analyser settings, same as the note mode
+    analyser_settings: AnalyserSettings,
+    filterbank: Filterbank,
+    stats: Arc<OscStats>,
+    //What the worker sent last, so feedback can skip echoes
+    sent: Arc<SentValues>,
+    //Latest param values for the worker when it sends at a fixed rate
+    snapshot: Arc<ParamSnapshot>,
+    //Set once the editor has been opened, OSC feedback goes through it
+    gui_context: Arc<RwLock<Option<Arc<dyn GuiContext>>>>,
     editor_state: Arc<ViziaState>,
     //Control
     control_dirty: Arc<AtomicBool>,
@@ -41,6 +117,9 @@ pub struct OsClaPhoton {
     offset_dirty: Arc<AtomicBool>,
 
     prev_params: [[f32; 5]; NUM_PARAMS],
+    sample_clock: SampleClock,
+    //Host transport state from the last block, to catch playback starting
+    was_playing: bool,
 
     //ToDo:
     //tilts params
@@ -78,6 +157,7 @@ impl Default for OsClaPhoton {
         let offset_dirty = Arc::new(AtomicBool::new(false));
 
         let channel = OscChannel::default();
+        let (server_sender, server_receiver) = crossbeam_channel::bounded(64);
         Self {
             params: Arc::new(OsClaPhotonParams::new(
 
@@ -94,6 +174,21 @@ impl Default for OsClaPhoton {
             osc_thread: None,
             sender: Arc::new(channel.sender),
             receiver: Some(channel.receiver),
+            server_thread: None,
+            server_sender: Arc::new(server_sender),
+            server_receiver: Some(server_receiver),
+            midi: Arc::new(MidiShared::default()),
+            midi_decoder: MidiDecoder::default(),
+            note_mode: NoteMode::default(),
+            note_player: NotePlayer::default(),
+            follower_settings: FollowerSettings::default(),
+            follower: EnvelopeFollower::default(),
+            analyser_settings: AnalyserSettings::default(),
+            filterbank: Filterbank::default(),
+            stats: Arc::new(OscStats::default()),
+            sent: Arc::new(SentValues::default()),
+            snapshot: Arc::new(ParamSnapshot::default()),
+            gui_context: Arc::new(RwLock::new(None)),
             editor_state: editor::default_state(),
             control_dirty,
             shutter_dirty,
@@ -105,6 +200,8 @@ impl Default for OsClaPhoton {
             zoom_speed_dirty,
             offset_dirty,
             prev_params: [[0.0; 5]; NUM_PARAMS],
+            sample_clock: SampleClock::default(),
+            was_playing: false,
         }
     }
 }
@@ -122,13 +219,13 @@ struct OscChannel {
 
 impl Default for OscChannel {
     fn default() -> Self {
-        let (sender, receiver) = crossbeam_channel::bounded(65_536);
+        let (sender, receiver) = crossbeam_channel::bounded(OSC_CHANNEL_CAPACITY);
         Self { sender, receiver }
     }
 }
 
 struct OscParamType {
-    name: String,
+    id: ParamId,
     value: f32,
 }
 
@@ -137,15 +234,90 @@ struct OscConnectionType {
     port: u16,
 }
 
+#[derive(Clone)]
+struct OscTransportType {
+    transport: OscTransport,
+    tcp_framing: TcpFraming,
+    multicast: OscMulticast,
+}
+
+struct OscHeadOutputType {
+    head_output: HeadOutput,
+}
+
+struct OscEnumArgsType {
+    enum_args: OscEnumArgs,
+}
+
+#[derive(Clone)]
+struct OscMappingsType {
+    mappings: OscMappings,
+}
+
+struct OscSmoothingsType {
+    smoothings: OscSmoothings,
+}
+
+struct OscOutputRateType {
+    output_rate: OutputRate,
+}
+
+struct OscHeartbeatType {
+    heartbeat: OscHeartbeat,
+}
+
+struct OscDestinationsType {
+    destinations: Vec<OscDestination>,
+}
+
 struct OscAddressBaseType {
     address: String,
 }
 
+#[derive(Clone)]
+struct OscAddressSchemaType {
+    schema: OscAddressSchema,
+}
+
+struct OscBlockEndType {
+    //UNIX seconds the block starts at, None sends right away
+    send_at: Option<f64>,
+}
+
 enum OscChannelMessageType {
     Exit,
     ConnectionChange(OscConnectionType),
+    DestinationsChange(OscDestinationsType),
+    TransportChange(OscTransportType),
+    HeartbeatChange(OscHeartbeatType),
     AddressBaseChange(OscAddressBaseType),
+    AddressSchemaChange(OscAddressSchemaType),
+    HeadOutputChange(OscHeadOutputType),
+    EnumArgsChange(OscEnumArgsType),
+    MappingsChange(OscMappingsType),
+    SmoothingsChange(OscSmoothingsType),
+    OutputRateChange(OscOutputRateType),
     Param(OscParamType),
+    //Everything since the last BlockEnd goes out as one bundle
+    BlockEnd(OscBlockEndType),
+    //Resend every param, e.g. for a receiver that started after us
+    SendAll,
+}
+
+struct OscListenPortType {
+    port: Option<u16>,
+}
+
+enum OscServerMessageType {
+    Exit,
+    ListenPortChange(OscListenPortType),
+    //The listener joins the multicast group when multicast is used
+    TransportChange(OscTransportType),
+    AddressBaseChange(OscAddressBaseType),
+    //The listener matches incoming addresses with the same schema we send with
+    AddressSchemaChange(OscAddressSchemaType),
+    //Incoming values are mapped back before they are applied
+    MappingsChange(OscMappingsType),
 }
 
 #[derive(Params)]
@@ -155,8 +327,56 @@ pub struct OsClaPhotonParams {
     osc_server_address: RwLock<String>,
     #[persist = "osc_server_port"]
     osc_server_port: RwLock<u16>,
+    #[persist = "osc_transport"]
+    osc_transport: RwLock<OscTransport>,
+    #[persist = "osc_tcp_framing"]
+    osc_tcp_framing: RwLock<TcpFraming>,
+    #[persist = "osc_multicast"]
+    osc_multicast: RwLock<OscMulticast>,
     #[persist = "osc_address_base"]
     osc_address_base: RwLock<String>,
+    #[persist = "osc_address_schema"]
+    osc_address_schema: RwLock<OscAddressSchema>,
+    #[persist = "osc_head_output"]
+    osc_head_output: RwLock<HeadOutput>,
+    #[persist = "osc_enum_args"]
+    osc_enum_args: RwLock<OscEnumArgs>,
+    #[persist = "osc_mappings"]
+    osc_mappings: RwLock<OscMappings>,
+    #[persist = "osc_smoothings"]
+    osc_smoothings: RwLock<OscSmoothings>,
+    #[persist = "osc_output_rate"]
+    osc_output_rate: RwLock<OutputRate>,
+    //Sent to on top of osc_server_address:osc_server_port
+    #[persist = "osc_destinations"]
+    osc_destinations: RwLock<Vec<OscDestination>>,
+    //None disables OSC feedback
+    #[persist = "osc_listen_port"]
+    osc_listen_port: RwLock<Option<u16>>,
+    #[persist = "osc_heartbeat"]
+    osc_heartbeat: RwLock<OscHeartbeat>,
+    #[persist = "midi_bindings"]
+    midi_bindings: RwLock<MidiBindings>,
+    #[persist = "midi_outputs"]
+    midi_outputs: RwLock<MidiOutputs>,
+    #[persist = "note_mode"]
+    note_mode: RwLock<NoteMode>,
+    #[persist = "audio_follower"]
+    audio_follower: RwLock<FollowerSettings>,
+    #[persist = "follower_routes"]
+    follower_routes: RwLock<FollowerRoutes>,
+    #[persist = "analyser"]
+    analyser: RwLock<AnalyserSettings>,
+    //Not persisted, what the note mode is playing on the heads right now
+    head_overrides: HeadOverrides,
+    //Not persisted, what the analyser bands play on the heads right now
+    band_overrides: HeadOverrides,
+    //Not persisted, what the envelope follower adds to the params right now
+    modulation: ParamModulation,
+    //Not persisted, what the bound MIDI controls play until the host moves the param
+    midi_overrides: ParamOverrides,
+    //Not persisted, feedback that came in before the editor was opened, same as the MIDI ones
+    feedback_overrides: ParamOverrides,
 
     //Setting Flags
     // #[id = "flag_send_midi"]
@@ -192,6 +412,26 @@ pub struct OsClaPhotonParams {
     tilt_params: [TiltParams; NUM_PARAMS],
 }
 
+/// An extra machine every packet is also sent to, e.g. a backup TD-Photon or a logging box
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OscDestination {
+    pub enabled: bool,
+    pub address: String,
+    pub port: u16,
+    /// Replaces `osc_address_base` for this destination when set
+    pub address_base: Option<String>,
+}
+
+impl Default for OscDestination {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            address: "127.0.0.1".to_string(),
+            port: 12345,
+            address_base: None,
+        }
+    }
+}
 
 #[derive(Enum, Debug, PartialEq)]
 enum Control {
@@ -247,6 +487,18 @@ struct TiltParams {
     pub blue: FloatParam,
 }
 
+impl TiltParams {
+    fn field(&self, field: HeadField) -> &FloatParam {
+        match field {
+            HeadField::Tilt => &self.tilt,
+            HeadField::Dimmer => &self.dimmer,
+            HeadField::Red => &self.red,
+            HeadField::Green => &self.green,
+            HeadField::Blue => &self.blue,
+        }
+    }
+}
+
 impl OsClaPhotonParams {
     #[allow(clippy::derivable_impls)]
     fn new(
@@ -264,7 +516,31 @@ impl OsClaPhotonParams {
         Self {
             osc_server_address: RwLock::new("255.255.255.255".to_string()),
             osc_server_port: RwLock::new(12345),
+            //Broadcast matches the default address and what older versions always did
+            osc_transport: RwLock::new(OscTransport::UdpBroadcast),
+            osc_tcp_framing: RwLock::new(TcpFraming::Slip),
+            osc_multicast: RwLock::new(OscMulticast::default()),
             osc_address_base: RwLock::new("photon_1".to_string()),
+            osc_address_schema: RwLock::new(OscAddressSchema::default()),
+            osc_head_output: RwLock::new(HeadOutput::default()),
+            osc_enum_args: RwLock::new(OscEnumArgs::default()),
+            osc_mappings: RwLock::new(OscMappings::default()),
+            osc_smoothings: RwLock::new(OscSmoothings::default()),
+            osc_output_rate: RwLock::new(OutputRate::default()),
+            osc_destinations: RwLock::new(Vec::new()),
+            osc_listen_port: RwLock::new(None),
+            osc_heartbeat: RwLock::new(OscHeartbeat::default()),
+            midi_bindings: RwLock::new(MidiBindings::default()),
+            midi_outputs: RwLock::new(MidiOutputs::default()),
+            note_mode: RwLock::new(NoteMode::default()),
+            audio_follower: RwLock::new(FollowerSettings::default()),
+            follower_routes: RwLock::new(FollowerRoutes::default()),
+            analyser: RwLock::new(AnalyserSettings::default()),
+            head_overrides: HeadOverrides::default(),
+            band_overrides: HeadOverrides::default(),
+            modulation: ParamModulation::default(),
+            midi_overrides: ParamOverrides::default(),
+            feedback_overrides: ParamOverrides::default(),
             // flag_send_midi: BoolParam::new("flag_send_midi", true)
             //     .hide()
             //     .non_automatable(),
@@ -280,64 +556,64 @@ impl OsClaPhotonParams {
             // .hide()
             // .non_automatable(),
 
-            control: EnumParam::new("Control", Control::Fast)
+            control: EnumParam::new(ParamId::Control.global_name(), Control::Fast)
                 .with_callback(Arc::new(move |_x| control_dirty.store(true, Ordering::Release))),
-            shutter: EnumParam::new("Shutter", Shutter::Open)
+            shutter: EnumParam::new(ParamId::Shutter.global_name(), Shutter::Open)
                 .with_callback(Arc::new(move |_x| shutter_dirty.store(true, Ordering::Release))),
-            rotation: EnumParam::new("Rotation", RotationControl::RotationStop)
+            rotation: EnumParam::new(ParamId::Rotation.global_name(), RotationControl::RotationStop)
                 .with_callback(Arc::new(move |_x| rotation_dirty.store(true, Ordering::Release))),
 
-            beam_number: FloatParam::new("Beams Number", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+            beam_number: FloatParam::new(ParamId::BeamNumber.global_name(), 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
                 .with_step_size(0.01)
                 .with_callback(Arc::new(move |_x| beam_number_dirty.store(true, Ordering::Release))),
-            rot_speed: FloatParam::new("Rotation Speed", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+            rot_speed: FloatParam::new(ParamId::RotSpeed.global_name(), 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
                 .with_step_size(0.0001)
                 .with_callback(Arc::new(move |_x| rot_speed_dirty.store(true, Ordering::Release))),
-            beam_size: FloatParam::new("Beams Size", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+            beam_size: FloatParam::new(ParamId::BeamSize.global_name(), 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
                 .with_step_size(0.0001)
                 .with_callback(Arc::new(move |_x| beam_size_dirty.store(true, Ordering::Release))),
-            zoom: FloatParam::new("Zoom", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+            zoom: FloatParam::new(ParamId::Zoom.global_name(), 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
                 .with_step_size(0.0001)
                 .with_callback(Arc::new(move |_x| zoom_dirty.store(true, Ordering::Release))),
-            zoom_speed: FloatParam::new("Zoom Speed", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+            zoom_speed: FloatParam::new(ParamId::ZoomSpeed.global_name(), 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
                 .with_step_size(0.0001)
                 .with_callback(Arc::new(move |_x| zoom_speed_dirty.store(true, Ordering::Release))),
-            offset: FloatParam::new("Offset", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+            offset: FloatParam::new(ParamId::Offset.global_name(), 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
                 .with_step_size(0.0001)
                 .with_callback(Arc::new(move |_x| offset_dirty.store(true, Ordering::Release))),
 
             //Tilts 
-            tilt_params: INDEXES.map(|index | TiltParams {
+            tilt_params: core::array::from_fn(|index| TiltParams {
                 tilt: FloatParam::new(
-                    format!("Tilt {index}"),
+                    ParamId::Head(index, HeadField::Tilt).name(),
                     0.0,
                     FloatRange::Linear { min: 0.0, max: 1.0 },
                 )
                 .with_step_size(0.0001),
 
                 dimmer: FloatParam::new(
-                    format!("Dimmer {index}"),
+                    ParamId::Head(index, HeadField::Dimmer).name(),
                     1.0,
                     FloatRange::Linear { min: 0.0, max: 1.0 },
                 )
                 .with_step_size(0.0001),
 
                 red: FloatParam::new(
-                    format!("Red {index}"),
+                    ParamId::Head(index, HeadField::Red).name(),
                     1.0,
                     FloatRange::Linear { min: 0.0, max: 1.0 },
                 )
                 .with_step_size(0.0001),
 
                 green: FloatParam::new(
-                    format!("Green {index}"),
+                    ParamId::Head(index, HeadField::Green).name(),
                     0.0,
                     FloatRange::Linear { min: 0.0, max: 1.0 },
                 )
                 .with_step_size(0.0001),
 
                 blue: FloatParam::new(
-                    format!("Blue {index}"),
+                    ParamId::Head(index, HeadField::Blue).name(),
                     0.0,
                     FloatRange::Linear { min: 0.0, max: 1.0 },
                 )
@@ -348,8 +624,161 @@ impl OsClaPhotonParams {
     }
 }
 
+impl OsClaPhotonParams {
+    fn transport_settings(&self) -> OscTransportType {
+        OscTransportType {
+            transport: *self.osc_transport.read(),
+            tcp_framing: *self.osc_tcp_framing.read(),
+            multicast: self.osc_multicast.read().clone(),
+        }
+    }
+
+    /// What the note mode, the analyser or a bound MIDI control plays instead of the param, in
+    /// that order where more than one plays it
+    fn played_value(&self, id: ParamId) -> Option<f32> {
+        let head = match id {
+            ParamId::Head(index, field) => self
+                .head_overrides
+                .get(index, field)
+                .or_else(|| self.band_overrides.get(index, field)),
+            _ => None,
+        };
+        head.or_else(|| self.midi_overrides.get(id))
+    }
+
+    /// The value we send for a param, enums as their variant index. Params something plays send
+    /// what it plays, see `played_value()`, then feedback waiting for an editor. The envelope
+    /// follower is added on top
+    fn osc_value(&self, id: ParamId) -> f32 {
+        let value = self
+            .played_value(id)
+            .or_else(|| self.feedback_overrides.get(id))
+            .unwrap_or_else(|| self.param_value(id));
+        if id.is_enum() {
+            value
+        } else {
+            //Every float param is 0-1
+            (value + self.modulation.offset(id)).clamp(0.0, 1.0)
+        }
+    }
+
+    /// What the host has for a param, enums as their variant index
+    fn param_value(&self, id: ParamId) -> f32 {
+        match id {
+            ParamId::Control => self.control.value().to_index() as f32,
+            ParamId::Shutter => self.shutter.value().to_index() as f32,
+            ParamId::Rotation => self.rotation.value().to_index() as f32,
+            ParamId::BeamNumber => self.beam_number.value(),
+            ParamId::RotSpeed => self.rot_speed.value(),
+            ParamId::BeamSize => self.beam_size.value(),
+            ParamId::Zoom => self.zoom.value(),
+            ParamId::ZoomSpeed => self.zoom_speed.value(),
+            ParamId::Offset => self.offset.value(),
+            ParamId::Head(index, field) => self
+                .tilt_params
+                .get(index)
+                .map_or(0.0, |tilt_params| tilt_params.field(field).value()),
+        }
+    }
+
+    /// Apply a value that came in over OSC, enums take their variant index like we send them. The
+    /// value is in the same terms as `osc_value()`, so what the follower adds is taken back off
+    /// and params something plays are left alone. Without a setter, before the editor was opened,
+    /// it is played as an override instead. Returns false if it was left alone
+    fn set_from_osc(&self, setter: Option<&ParamSetter>, id: ParamId, value: f32) -> bool {
+        if self.played_value(id).is_some() {
+            nih_trace!("{} is played, ignoring feedback", id.name());
+            return false;
+        }
+        let value = if id.is_enum() {
+            value
+        } else {
+            (value - self.modulation.offset(id)).clamp(0.0, 1.0)
+        };
+        match setter {
+            Some(setter) => self.set_param_value(setter, id, value),
+            None => self.feedback_overrides.set(id, value),
+        }
+        true
+    }
+
+    /// Set a param through the host like a GUI edit so it can be recorded as automation, enums
+    /// take their variant index. The value is stored as is
+    fn set_param_value(&self, setter: &ParamSetter, id: ParamId, value: f32) {
+        match id {
+            ParamId::Control => set_param(setter, &self.control, enum_from_value(value)),
+            ParamId::Shutter => set_param(setter, &self.shutter, enum_from_value(value)),
+            ParamId::Rotation => set_param(setter, &self.rotation, enum_from_value(value)),
+            ParamId::BeamNumber => set_param(setter, &self.beam_number, value),
+            ParamId::RotSpeed => set_param(setter, &self.rot_speed, value),
+            ParamId::BeamSize => set_param(setter, &self.beam_size, value),
+            ParamId::Zoom => set_param(setter, &self.zoom, value),
+            ParamId::ZoomSpeed => set_param(setter, &self.zoom_speed, value),
+            ParamId::Offset => set_param(setter, &self.offset, value),
+            ParamId::Head(index, field) => {
+                if let Some(tilt_params) = self.tilt_params.get(index) {
+                    set_param(setter, tilt_params.field(field), value);
+                }
+            }
+        }
+    }
+}
+
+fn set_param<P: Param>(setter: &ParamSetter, param: &P, value: P::Plain)
+where
+    P::Plain: PartialEq,
+{
+    //Skip values we already have, echoes of what we sent are dropped before they get here
+    if param.unmodulated_plain_value() == value {
+        return;
+    }
+    setter.begin_set_parameter(param);
+    setter.set_parameter(param, value);
+    setter.end_set_parameter(param);
+}
+
+/// `#[id]`s of an enum param's variants in index order, empty for float params
+pub(crate) fn enum_variant_ids(id: ParamId) -> &'static [&'static str] {
+    let ids = match id {
+        ParamId::Control => Control::ids(),
+        ParamId::Shutter => Shutter::ids(),
+        ParamId::Rotation => RotationControl::ids(),
+        _ => None,
+    };
+    ids.unwrap_or_default()
+}
+
+/// Display names of an enum param's variants in index order, empty for float params
+pub(crate) fn enum_variant_names(id: ParamId) -> &'static [&'static str] {
+    match id {
+        ParamId::Control => Control::variants(),
+        ParamId::Shutter => Shutter::variants(),
+        ParamId::Rotation => RotationControl::variants(),
+        _ => &[],
+    }
+}
+
+/// Variants that reset part of the rig, the editor asks for a confirm click before setting them
+pub(crate) fn is_destructive_variant(id: ParamId, index: usize) -> bool {
+    if index >= enum_variant_names(id).len() {
+        return false;
+    }
+    match id {
+        ParamId::Control => matches!(
+            Control::from_index(index),
+            Control::ResetAll | Control::ResetMotor | Control::ResetSource
+        ),
+        ParamId::Rotation => RotationControl::from_index(index) == RotationControl::RotationReset,
+        _ => false,
+    }
+}
+
+fn enum_from_value<T: Enum>(value: f32) -> T {
+    T::from_index((value.max(0.0) as usize).min(T::variants().len() - 1))
+}
+
 impl Plugin for OsClaPhoton {
-    const NAME: &'static str = "OSCLAPHOTON";
+    const NAME: &'static str = PLUGIN_VARIANT.0;
     const VENDOR: &'static str = "Hawtin Labs";
     const URL: &'static str = "https://github.com/Hawtin-Labs/OSCLAPHOTON";
     const EMAIL: &'static str = "";
@@ -383,6 +812,10 @@ impl Plugin for OsClaPhoton {
         editor::create(
             self.params.clone(),
             self.sender.clone(),
+            self.server_sender.clone(),
+            self.stats.clone(),
+            self.midi.clone(),
+            self.gui_context.clone(),
             self.editor_state.clone(),
         )
     }
@@ -400,50 +833,113 @@ impl Plugin for OsClaPhoton {
             return false;
         }
 
+        self.sample_clock
+            .reset(buffer_config.sample_rate, buffer_config.max_buffer_size);
+
+        //The bindings may have come in with a restored state
+        self.midi.set_bindings(&self.params.midi_bindings.read());
+        self.midi.set_outputs(&self.params.midi_outputs.read());
+        self.params
+            .modulation
+            .set_routes(&self.params.follower_routes.read());
+
         //Setup OSC background thread
         //Dont remake the background thread if its already running
         if self.osc_thread.is_none() {
-            let socket = match UdpSocket::bind("0.0.0.0:0") {
+            let socket = match UdpSockets::bind() {
                 Ok(socket) => socket,
                 Err(e) => {
                     nih_error!("Failed to bind socket {:?}", e);
                     return false;
                 }
             };
-            let ip_port = format!(
-                "{}:{}",
-                *self.params.osc_server_address.read(),
-                *self.params.osc_server_port.read()
-            );
-            nih_trace!("Connecting: {}", ip_port);
-            
-            let _ = socket.set_broadcast(true);
-
-            let connect_result = socket.connect(&ip_port);
-            if connect_result.is_err() {
-                nih_error!(
-                    "Failed to connect socket to {} {:?}",
-                    ip_port,
-                    connect_result.unwrap_err()
-                );
-                return false;
-            }
+            let connection = OscConnectionType {
+                ip: self.params.osc_server_address.read().to_string(),
+                port: *self.params.osc_server_port.read(),
+            };
+            nih_trace!("Connecting: {}:{}", connection.ip, connection.port);
 
-            nih_trace!("Connected!");
-            nih_trace!("Connected to: {}", ip_port);
+            let transport = self.params.transport_settings();
+            socket.configure(transport.transport, &transport.multicast);
 
+            let destinations = self.params.osc_destinations.read().clone();
             let address_base = self.params.osc_address_base.read().to_string();
             nih_trace!("OSC Address Base: {}", address_base);
+            let schema = self.params.osc_address_schema.read().clone();
+            let head_output = *self.params.osc_head_output.read();
+            let enum_args = self.params.osc_enum_args.read().clone();
+            let mappings = self.params.osc_mappings.read().clone();
+            let smoothings = self.params.osc_smoothings.read().clone();
+            let heartbeat = self.params.osc_heartbeat.read().clone();
+            let output_rate = *self.params.osc_output_rate.read();
+            let snapshot = self.snapshot.clone();
+            let params = self.params.clone();
+            let stats = self.stats.clone();
+            let sent = self.sent.clone();
 
             if let Some(receiver) = std::mem::replace(&mut self.receiver, None) {
-                let client_thread =
-                    thread::spawn(move || osc_client_worker(socket, address_base, receiver));
+                let client_thread = thread::spawn(move || {
+                    //Resolving can block on DNS so it happens on the worker
+                    let mut client = OscClient::new(
+                        socket,
+                        connection,
+                        transport,
+                        &destinations,
+                        &address_base,
+                        schema,
+                        stats,
+                        sent,
+                    );
+                    client.head_output = head_output;
+                    client.enum_args = enum_args;
+                    client.mappings = mappings;
+                    osc_client_worker(
+                        client,
+                        smoothings,
+                        heartbeat,
+                        output_rate,
+                        snapshot,
+                        params,
+                        receiver,
+                    )
+                });
 
                 self.osc_thread = Some(client_thread);
             } else {
                 nih_error!("Failed get thread channel receiver");
                 return false;
             }
+
+            let listen_port = *self.params.osc_listen_port.read();
+            let transport = self.params.transport_settings();
+            let address_base = self.params.osc_address_base.read().to_string();
+            let schema = self.params.osc_address_schema.read().clone();
+            let mappings = self.params.osc_mappings.read().clone();
+            let handles = feedback::FeedbackHandles {
+                params: self.params.clone(),
+                stats: self.stats.clone(),
+                sent: self.sent.clone(),
+                client_sender: self.sender.clone(),
+                gui_context: self.gui_context.clone(),
+            };
+            if let Some(receiver) = std::mem::replace(&mut self.server_receiver, None) {
+                let server_thread = thread::spawn(move || {
+                    feedback::osc_server_worker(
+                        listen_port,
+                        transport,
+                        address_base,
+                        schema,
+                        mappings,
+                        handles,
+                        receiver,
+                    )
+                });
+
+                self.server_thread = Some(server_thread);
+            } else {
+                nih_error!("Failed get server thread channel receiver");
+                return false;
+            }
         } else {
             //Threads already alive just update params
             let connection_send_result =
@@ -458,8 +954,156 @@ impl Plugin for OsClaPhoton {
                     connection_send_result.unwrap_err()
                 );
             }
+            let transport_send_result = self.sender.send(OscChannelMessageType::TransportChange(
+                self.params.transport_settings(),
+            ));
+            if transport_send_result.is_err() {
+                nih_error!(
+                    "Failed to send TransportChange update {:?}",
+                    transport_send_result.unwrap_err()
+                );
+            }
+            let server_transport_send_result = self.server_sender.send(
+                OscServerMessageType::TransportChange(self.params.transport_settings()),
+            );
+            if server_transport_send_result.is_err() {
+                nih_error!(
+                    "Failed to send server TransportChange update {:?}",
+                    server_transport_send_result.unwrap_err()
+                );
+            }
+            let heartbeat_send_result =
+                self.sender
+                    .send(OscChannelMessageType::HeartbeatChange(OscHeartbeatType {
+                        heartbeat: self.params.osc_heartbeat.read().clone(),
+                    }));
+            if heartbeat_send_result.is_err() {
+                nih_error!(
+                    "Failed to send HeartbeatChange update {:?}",
+                    heartbeat_send_result.unwrap_err()
+                );
+            }
+            let destinations_send_result =
+                self.sender
+                    .send(OscChannelMessageType::DestinationsChange(OscDestinationsType {
+                        destinations: self.params.osc_destinations.read().clone(),
+                    }));
+            if destinations_send_result.is_err() {
+                nih_error!(
+                    "Failed to send DestinationsChange update {:?}",
+                    destinations_send_result.unwrap_err()
+                );
+            }
+            let head_output_send_result =
+                self.sender
+                    .send(OscChannelMessageType::HeadOutputChange(OscHeadOutputType {
+                        head_output: *self.params.osc_head_output.read(),
+                    }));
+            if head_output_send_result.is_err() {
+                nih_error!(
+                    "Failed to send HeadOutputChange update {:?}",
+                    head_output_send_result.unwrap_err()
+                );
+            }
+            let enum_args_send_result =
+                self.sender
+                    .send(OscChannelMessageType::EnumArgsChange(OscEnumArgsType {
+                        enum_args: self.params.osc_enum_args.read().clone(),
+                    }));
+            if enum_args_send_result.is_err() {
+                nih_error!(
+                    "Failed to send EnumArgsChange update {:?}",
+                    enum_args_send_result.unwrap_err()
+                );
+            }
+            let smoothings_send_result =
+                self.sender
+                    .send(OscChannelMessageType::SmoothingsChange(OscSmoothingsType {
+                        smoothings: self.params.osc_smoothings.read().clone(),
+                    }));
+            if smoothings_send_result.is_err() {
+                nih_error!(
+                    "Failed to send SmoothingsChange update {:?}",
+                    smoothings_send_result.unwrap_err()
+                );
+            }
+            let output_rate_send_result =
+                self.sender
+                    .send(OscChannelMessageType::OutputRateChange(OscOutputRateType {
+                        output_rate: *self.params.osc_output_rate.read(),
+                    }));
+            if output_rate_send_result.is_err() {
+                nih_error!(
+                    "Failed to send OutputRateChange update {:?}",
+                    output_rate_send_result.unwrap_err()
+                );
+            }
+            let mappings = OscMappingsType {
+                mappings: self.params.osc_mappings.read().clone(),
+            };
+            let mappings_send_result = self
+                .sender
+                .send(OscChannelMessageType::MappingsChange(mappings.clone()));
+            if mappings_send_result.is_err() {
+                nih_error!(
+                    "Failed to send MappingsChange update {:?}",
+                    mappings_send_result.unwrap_err()
+                );
+            }
+            let server_mappings_send_result = self
+                .server_sender
+                .send(OscServerMessageType::MappingsChange(mappings));
+            if server_mappings_send_result.is_err() {
+                nih_error!(
+                    "Failed to send server MappingsChange update {:?}",
+                    server_mappings_send_result.unwrap_err()
+                );
+            }
+            let schema = OscAddressSchemaType {
+                schema: self.params.osc_address_schema.read().clone(),
+            };
+            let schema_send_result = self
+                .sender
+                .send(OscChannelMessageType::AddressSchemaChange(schema.clone()));
+            if schema_send_result.is_err() {
+                nih_error!(
+                    "Failed to send AddressSchemaChange update {:?}",
+                    schema_send_result.unwrap_err()
+                );
+            }
+            let server_schema_send_result = self
+                .server_sender
+                .send(OscServerMessageType::AddressSchemaChange(schema));
+            if server_schema_send_result.is_err() {
+                nih_error!(
+                    "Failed to send server AddressSchemaChange update {:?}",
+                    server_schema_send_result.unwrap_err()
+                );
+            }
             let address_base = self.params.osc_address_base.read().to_string();
             nih_trace!("OSC Address Base: {}", address_base);
+            let listen_send_result =
+                self.server_sender
+                    .send(OscServerMessageType::ListenPortChange(OscListenPortType {
+                        port: *self.params.osc_listen_port.read(),
+                    }));
+            if listen_send_result.is_err() {
+                nih_error!(
+                    "Failed to send ListenPortChange update {:?}",
+                    listen_send_result.unwrap_err()
+                );
+            }
+            let server_address_send_result = self.server_sender.send(
+                OscServerMessageType::AddressBaseChange(OscAddressBaseType {
+                    address: address_base.clone(),
+                }),
+            );
+            if server_address_send_result.is_err() {
+                nih_error!(
+                    "Failed to send server AddressBaseChange update {:?}",
+                    server_address_send_result.unwrap_err()
+                );
+            }
             let address_send_result = self.sender.send(OscChannelMessageType::AddressBaseChange(
                 OscAddressBaseType {
                     address: address_base,
@@ -486,212 +1130,222 @@ impl Plugin for OsClaPhoton {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
+        //Nothing in here may allocate, params go out as a ParamId and the worker resolves the address
+        let mut block_dirty = false;
 
-        //Process Dirty Control Params
-        if self.control_dirty
-            .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
-        {
-            //nih_trace!("Param Dirty: {} {}", self.params.control.name(), self.params.control.value());
-            let _ = self.sender
-                .send(OscChannelMessageType::Param(OscParamType {
-                    name: self.params.control.name().to_string(), //TODO: allocation
-                    value: self.params.control.value().to_index() as f32,
-                }));
+        //Skipped for this block if the editor is writing it
+        if let Some(note_mode) = self.params.note_mode.try_read() {
+            self.note_mode = *note_mode;
         }
-
-        if self.shutter_dirty
-            .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
-        {
-            //nih_trace!("Param Dirty: {} {}", self.params.shutter.name(), self.params.shutter.value());
-            let _ = self.sender
-                .send(OscChannelMessageType::Param(OscParamType {
-                    name: self.params.shutter.name().to_string(), //TODO: allocation
-                    value: self.params.shutter.value().to_index() as f32,
-                }));
+        let note_mode = self.note_mode;
+
+        //Bound MIDI controls play their params like the note mode plays the heads. Notes on the
+        //note mode channel play the heads
+        while let Some(event) = context.next_event() {
+            match event {
+                NoteEvent::MidiCC { channel, cc, value, .. } => {
+                    let midi = &self.midi;
+                    self.midi_decoder.decode(channel, cc, (value * 127.0).round() as u8, |control, x| {
+                        midi.handle(control, x)
+                    });
+                }
+                NoteEvent::NoteOn { channel, note, velocity, .. }
+                    if note_mode.enabled && channel == note_mode.channel =>
+                {
+                    if velocity > 0.0 {
+                        self.note_player
+                            .note_on(&note_mode, note, velocity, &self.params.head_overrides);
+                    } else {
+                        self.note_player.note_off(&note_mode, note);
+                    }
+                }
+                NoteEvent::NoteOff { channel, note, .. }
+                    if note_mode.enabled && channel == note_mode.channel =>
+                {
+                    self.note_player.note_off(&note_mode, note);
+                }
+                _ => {}
+            }
         }
-
-        if self.rotation_dirty
-            .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
-        {
-            //nih_trace!("Param Dirty: {} {}", self.params.rotation.name(), self.params.rotation.value());
-            let _ = self.sender
-                .send(OscChannelMessageType::Param(OscParamType {
-                    name: self.params.rotation.name().to_string(), //TODO: allocation
-                    value: self.params.rotation.value().to_index() as f32,
-                }));
+        self.note_player.render(&note_mode, &self.params.head_overrides);
+        //Bound controls and feedback that had no editor to go through play their params until the
+        //host moves them. Globals and enums are only sent when flagged
+        let flag_dirty = |id: ParamId| {
+            if let Some(param_dirty) = self.dirty_flag(id) {
+                param_dirty.store(true, Ordering::Release);
+            }
+        };
+        let params = &self.params;
+        self.midi.render(&params.midi_overrides, flag_dirty);
+        params
+            .midi_overrides
+            .update(|id| params.param_value(id), flag_dirty);
+        params
+            .feedback_overrides
+            .update(|id| params.param_value(id), flag_dirty);
+
+        //Envelope follower on the input, the audio passes through untouched
+        if let Some(follower_settings) = self.params.audio_follower.try_read() {
+            self.follower_settings = *follower_settings;
         }
+        let level = self.follower.process(
+            &self.follower_settings,
+            context.transport().sample_rate,
+            buffer,
+        );
+        //Globals are only sent when flagged
+        self.params.modulation.apply(level, |id| {
+            if let Some(param_dirty) = self.dirty_flag(id) {
+                param_dirty.store(true, Ordering::Release);
+            }
+        });
 
-        //Process Dirty Global Params
-        let param_result = self.process_global_params();
-        if param_result.is_err() {
-            nih_error!("Failed to send params {:?}", param_result.unwrap_err());
+        //Band per head, same as the follower it only listens
+        if let Some(analyser_settings) = self.params.analyser.try_read() {
+            self.analyser_settings = *analyser_settings;
         }
+        self.filterbank.process(
+            &self.analyser_settings,
+            context.transport().sample_rate,
+            buffer,
+            &self.params.band_overrides,
+        );
+
+        if self.snapshot.is_enabled() {
+            //Fixed output rate, the worker picks the values up on its own clock and works out
+            //what changed
+            self.snapshot.publish(&self.params);
+        } else {
+            //Process Dirty Control Params
+            block_dirty |= self.process_control_params();
 
-        let mut param_temp: f32 = 0.0; //self.params[idx].value();
-        for idx in 0..NUM_PARAMS {
-            //self.send_tilt_param(&val, &self.params.tilt_params[idx])
-
-            //tilt
-            param_temp = self.params.tilt_params[idx].tilt.value();
-            if param_temp != self.prev_params[idx][0]
-            {
-                let _ = self.sender
-                    .send(OscChannelMessageType::Param(OscParamType {
-                    name: self.params.tilt_params[idx].tilt.name().to_string(), //TODO: allocation
-                    value: self.params.tilt_params[idx].tilt.value(),
-                }));
-            }
-            self.prev_params[idx][0] = param_temp;
-
-            //dimmer
-            param_temp = self.params.tilt_params[idx].dimmer.value();
-            if param_temp != self.prev_params[idx][1]
-            {
-                let _ = self.sender
-                    .send(OscChannelMessageType::Param(OscParamType {
-                    name: self.params.tilt_params[idx].dimmer.name().to_string(),
-                    value: self.params.tilt_params[idx].dimmer.value(),
-                }));
-            }
-            self.prev_params[idx][1] = param_temp;
+            //Process Dirty Global Params
+            block_dirty |= self.process_global_params();
 
-            //red
-            param_temp = self.params.tilt_params[idx].red.value();
-            if param_temp != self.prev_params[idx][2]
-            {
-                let _ = self.sender
-                    .send(OscChannelMessageType::Param(OscParamType {
-                    name: self.params.tilt_params[idx].red.name().to_string(),
-                    value: self.params.tilt_params[idx].red.value(),
-                }));
-            }
-            self.prev_params[idx][2] = param_temp;
+            //Process Changed Tilt Params
+            block_dirty |= self.process_tilt_params();
+        }
 
-            //green
-            param_temp = self.params.tilt_params[idx].green.value();
-            if param_temp != self.prev_params[idx][3]
-            {
-                let _ = self.sender
-                    .send(OscChannelMessageType::Param(OscParamType {
-                    name: self.params.tilt_params[idx].green.name().to_string(),
-                    value: self.params.tilt_params[idx].green.value(),
+        //Mirror the outgoing values on the MIDI output for motor faders, LED rings and desks
+        let params = &self.params;
+        self.midi.mirror(
+            |id| params.osc_value(id),
+            |channel, cc, value| {
+                context.send_event(NoteEvent::MidiCC {
+                    timing: 0,
+                    channel,
+                    cc,
+                    value: value as f32 / 127.0,
+                })
+            },
+        );
+
+        //Close the block so the worker sends everything from it as one bundle. With sample
+        //accurate automation each call starts at an automation point, so this is timed to the sample
+        if block_dirty {
+            let block_end_result = self.sender
+                .try_send(OscChannelMessageType::BlockEnd(OscBlockEndType {
+                    send_at: self.sample_clock.send_at(context.transport()),
                 }));
+            if block_end_result.is_err() {
+                self.stats.record_dropped();
             }
-            self.prev_params[idx][3] = param_temp;
-
-            //blue
-            param_temp = self.params.tilt_params[idx].blue.value();
-            if param_temp != self.prev_params[idx][4]
-            {
-                let _ = self.sender
-                    .send(OscChannelMessageType::Param(OscParamType {
-                    name: self.params.tilt_params[idx].blue.name().to_string(),
-                    value: self.params.tilt_params[idx].blue.value(),
-                }));
+        }
+        self.sample_clock.advance(buffer.samples());
+        self.note_player.advance(
+            &note_mode,
+            buffer.samples() as f32 / context.transport().sample_rate,
+        );
+
+        //Bring the receiver up to date when playback starts
+        let playing = context.transport().playing;
+        if playing && !self.was_playing {
+            let send_all_result = self.sender.try_send(OscChannelMessageType::SendAll);
+            if send_all_result.is_err() {
+                self.stats.record_dropped();
             }
-            self.prev_params[idx][4] = param_temp;
-           
-        };
-
-
+        }
+        self.was_playing = playing;
 
         ProcessStatus::Normal
     }
 }
 
 impl OsClaPhoton {
-    // fn process_control_params(&self) -> Result<()> {
-
-    //     self.send_dirty_control_param(&self.control_dirty, &self.params.control)?;
-    //     self.send_dirty_control_param(&self.shutter_dirty, &self.params.shutter)?;
-    //     self.send_dirty_control_param(&self.rotation_dirty, &self.params.rotation)?;
-    //     Ok(())
-    // }
-    // fn send_dirty_control_param(&self, param_dirty: &Arc<AtomicBool>, param: &EnumParam) -> Result<()> {
-    //     if param_dirty
-    //         .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
-    //         .is_ok()
-    //     {
-    //         nih_trace!("Param Dirty: {} {}", param.name(), param.value());
-    //         self.sender
-    //             .send(OscChannelMessageType::Param(OscParamType {
-    //                 name: param.name().to_string(), //TODO: allocation
-    //                 value: param.value(),
-    //             }))?;
-    //     }
-    //     Ok(())
-    // }
-
-    fn process_global_params(&self) -> Result<()> {
-
-        self.send_dirty_param(&self.beam_number_dirty, &self.params.beam_number)?;
-        self.send_dirty_param(&self.rot_speed_dirty, &self.params.rot_speed)?;
-        self.send_dirty_param(&self.beam_size_dirty, &self.params.beam_size)?;
-        self.send_dirty_param(&self.zoom_dirty, &self.params.zoom)?;
-        self.send_dirty_param(&self.zoom_speed_dirty, &self.params.zoom_speed)?;
-        self.send_dirty_param(&self.offset_dirty, &self.params.offset)?;
-        Ok(())
-    }
-
-    fn send_dirty_param(&self, param_dirty: &Arc<AtomicBool>, param: &FloatParam) -> Result<()> {
+    /// Returns true if any control param was sent
+    fn process_control_params(&self) -> bool {
+        let mut sent = false;
+        sent |= self.send_dirty_param(&self.control_dirty, ParamId::Control);
+        sent |= self.send_dirty_param(&self.shutter_dirty, ParamId::Shutter);
+        sent |= self.send_dirty_param(&self.rotation_dirty, ParamId::Rotation);
+        sent
+    }
+
+    /// Returns true if any global param was sent
+    fn process_global_params(&self) -> bool {
+        let mut sent = false;
+        sent |= self.send_dirty_param(&self.beam_number_dirty, ParamId::BeamNumber);
+        sent |= self.send_dirty_param(&self.rot_speed_dirty, ParamId::RotSpeed);
+        sent |= self.send_dirty_param(&self.beam_size_dirty, ParamId::BeamSize);
+        sent |= self.send_dirty_param(&self.zoom_dirty, ParamId::Zoom);
+        sent |= self.send_dirty_param(&self.zoom_speed_dirty, ParamId::ZoomSpeed);
+        sent |= self.send_dirty_param(&self.offset_dirty, ParamId::Offset);
+        sent
+    }
+
+    fn send_dirty_param(&self, param_dirty: &AtomicBool, id: ParamId) -> bool {
         if param_dirty
             .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
         {
-            nih_trace!("Param Dirty: {} {}", param.name(), param.value());
-            self.sender
-                .send(OscChannelMessageType::Param(OscParamType {
-                    name: param.name().to_string(), //TODO: allocation
-                    value: param.value(),
-                }))?;
-        }
-        Ok(())
-    }
-
-
-
-    // fn process_titl_params(&self) -> Result<()>{
-    //     let mut param_temp: f32 = 0.0; //self.params[idx].value();
-    //     for idx in 0..NUM_PARAMS {
-    //         //self.send_tilt_param(&val, &self.params.tilt_params[idx])
-
-    //         //tilt
-    //         param_temp = self.params.tilt_params[idx].tilt.value();
-    //         if param_temp != self.prev_params[idx][0]
-    //         {
-    //             let _ = self.sender
-    //                 .send(OscChannelMessageType::Param(OscParamType {
-    //                 name: self.params.tilt_params[idx].tilt.name().to_string(), //TODO: allocation
-    //                 value: self.params.tilt_params[idx].tilt.value(),
-    //             }));
-    //         }
-        
-    //     self.prev_params[idx][0] = param_temp;
-            
-    //     };
-
-    //     Ok(())
-    // }
+            return self.send_param(id, self.params.osc_value(id));
+        }
+        false
+    }
 
-    // fn send_tilt_param(&self, param_temp: f32, param_group: &TiltParams) -> Result<()> {
+    fn dirty_flag(&self, id: ParamId) -> Option<&Arc<AtomicBool>> {
+        match id {
+            ParamId::Control => Some(&self.control_dirty),
+            ParamId::Shutter => Some(&self.shutter_dirty),
+            ParamId::Rotation => Some(&self.rotation_dirty),
+            ParamId::BeamNumber => Some(&self.beam_number_dirty),
+            ParamId::RotSpeed => Some(&self.rot_speed_dirty),
+            ParamId::BeamSize => Some(&self.beam_size_dirty),
+            ParamId::Zoom => Some(&self.zoom_dirty),
+            ParamId::ZoomSpeed => Some(&self.zoom_speed_dirty),
+            ParamId::Offset => Some(&self.offset_dirty),
+            //Heads are compared against the last value anyway
+            ParamId::Head(..) => None,
+        }
+    }
 
-    //     param_temp = param_group.tilt.value();
-    //     //tilt
-    //     if param_temp != self.prev_params[idx][0]
-    //     {
-    //         let _ = self.sender
-    //         .send(OscChannelMessageType::Param(OscParamType {
-    //             name: self.params.osc_params[idx].osc_param.name().to_string(), //TODO: allocation
-    //             value: self.params.osc_params[idx].osc_param.value(),
-    //         }));
-    //     }
-    //     self.prev_params[idx] = param_temp;
+    /// Tilt params have no callbacks, they are compared against the last value we saw. This also
+    /// picks up what the note mode and the analyser play and what the follower adds. Returns true
+    /// if any tilt param was sent
+    fn process_tilt_params(&mut self) -> bool {
+        let mut sent = false;
+        for idx in 0..NUM_PARAMS {
+            for (field_idx, field) in HeadField::ALL.into_iter().enumerate() {
+                let value = self.params.osc_value(ParamId::Head(idx, field));
+                if value != self.prev_params[idx][field_idx] {
+                    sent |= self.send_param(ParamId::Head(idx, field), value);
+                }
+                self.prev_params[idx][field_idx] = value;
+            }
+        }
+        sent
+    }
 
-    //     Ok(())
-    // }
+    /// Never blocks the audio thread, if the worker falls behind the change is dropped
+    fn send_param(&self, id: ParamId, value: f32) -> bool {
+        let sent = self.sender
+            .try_send(OscChannelMessageType::Param(OscParamType { id, value }))
+            .is_ok();
+        if !sent {
+            self.stats.record_dropped();
+        }
+        sent
+    }
 
     fn kill_background_thread(&mut self) {
         let exit_result = self.sender.send(OscChannelMessageType::Exit);
@@ -702,71 +1356,671 @@ impl OsClaPhoton {
             );
         }
         self.osc_thread = None;
+
+        let server_exit_result = self.server_sender.send(OscServerMessageType::Exit);
+        if server_exit_result.is_err() {
+            nih_error!(
+                "Failed to send shutdown to server thread {:?}",
+                server_exit_result.unwrap_err()
+            );
+        }
+        self.server_thread = None;
     }
 }
 
 // /<osc_address_base>/param/<param_name>
 
+/// A resolved place the worker sends to
+struct OscTarget {
+    link: Option<OscLink>,
+    //Formatted override, None uses the plugin wide address base
+    address_base: Option<String>,
+    //Addresses for the override, None uses the plugin wide ones
+    addresses: Option<AddressTable>,
+    //TCP only, enum changes that didn't get through in order, they are cues like ResetAll
+    missed_cues: Vec<OscParamType>,
+    //TCP only, something didn't get through so the full state goes out once the link is back
+    stale: bool,
+}
+
+impl OscTarget {
+    /// Remember what a TCP link that is down didn't get
+    fn missed(&mut self, params: &[OscParamType]) {
+        self.stale = true;
+        let cues = params.iter().filter(|param| param.id.is_enum());
+        self.missed_cues.extend(cues.map(|param| OscParamType {
+            id: param.id,
+            value: param.value,
+        }));
+        //Oldest first, a long outage only keeps the latest cues
+        let excess = self.missed_cues.len().saturating_sub(TCP_MAX_MISSED_CUES);
+        self.missed_cues.drain(..excess);
+    }
+
+    fn resolve(ip: &str, port: u16, address_base: Option<&str>, stats: &OscStats) -> Self {
+        let addr = match (ip, port).to_socket_addrs() {
+            Ok(mut addrs) => addrs.next(),
+            Err(e) => {
+                nih_error!("Failed to resolve {}:{} {:?}", ip, port, e);
+                stats.record_error(format!("Failed to resolve {}:{} {}", ip, port, e));
+                None
+            }
+        };
+        Self {
+            link: addr.map(OscLink::new),
+            address_base: address_base.map(format_osc_address_base),
+            addresses: None,
+            missed_cues: Vec::new(),
+            stale: false,
+        }
+    }
+}
+
+/// The main connection, multicast sends to the group instead of the address
+fn resolve_main_target(
+    connection: &OscConnectionType,
+    transport: &OscTransportType,
+    stats: &OscStats,
+) -> OscTarget {
+    if transport.transport == OscTransport::UdpMulticast {
+        OscTarget::resolve(&transport.multicast.group, connection.port, None, stats)
+    } else {
+        OscTarget::resolve(&connection.ip, connection.port, None, stats)
+    }
+}
+
+fn resolve_destinations<'a>(
+    destinations: &'a [OscDestination],
+    stats: &'a OscStats,
+) -> impl Iterator<Item = OscTarget> + 'a {
+    destinations
+        .iter()
+        .filter(|destination| destination.enabled)
+        //The editor doesn't persist these, older state might still have them
+        .filter(|destination| {
+            let valid = destination.address_base.as_deref().map_or(true, is_valid_address_base);
+            if !valid {
+                nih_error!("Skipping {} with an invalid address base", destination.address);
+            }
+            valid
+        })
+        .map(move |destination| {
+            OscTarget::resolve(
+                &destination.address,
+                destination.port,
+                destination.address_base.as_deref(),
+                stats,
+            )
+        })
+}
+
 fn osc_client_worker(
-    socket: UdpSocket,
-    param_address_base: String,
+    client: OscClient,
+    smoothings: OscSmoothings,
+    heartbeat: OscHeartbeat,
+    output_rate: OutputRate,
+    snapshot: Arc<ParamSnapshot>,
+    params: Arc<OsClaPhotonParams>,
     recv: Receiver<OscChannelMessageType>,
 ) -> () {
     nih_trace!("Background thread spawned!");
-    nih_trace!("Background thread OSC Address Base: {}", param_address_base);
-    let mut address_base = format_osc_address_base(&param_address_base);
-    let mut connected = true; //We assume the socket we get is good
-    while let Some(channel_message) = recv.recv().ok() {
-        let osc_message = match channel_message {
+    nih_trace!("Background thread OSC Address Base: {}", client.address_base);
+    let mut client = client;
+    let mut smoothers = Smoothers::new(smoothings, client.sent.clone());
+    //Persisted values are not checked, keep them in range before they become `Duration`s
+    let mut heartbeat = heartbeat.clamped();
+    let mut output_rate = output_rate;
+    //Only set with a fixed output rate
+    let mut next_output = output_rate.hz().map(|_| unix_now_secs());
+    snapshot.set_enabled(next_output.is_some());
+    smoothers.set_frame_rate(output_rate.hz().unwrap_or(SMOOTHING_FRAME_RATE));
+    let mut heartbeat_counter: i32 = 0;
+    let mut next_heartbeat = unix_now_secs();
+    //Last time a block or the full state went out, for the idle resync
+    let mut last_activity = unix_now_secs();
+    let mut pending: Vec<OscParamType> = Vec::new();
+    //Blocks waiting for their timetag, sorted by send time
+    let mut scheduled: VecDeque<(f64, Vec<OscParamType>)> = VecDeque::new();
+    //Whatever is listening gets the current state straight away
+    send_full_state(&mut client, &mut smoothers, &mut scheduled, &params);
+    loop {
+        client.poll_links();
+        let now = unix_now_secs();
+        if heartbeat.enabled && now >= next_heartbeat {
+            heartbeat_counter = heartbeat_counter.wrapping_add(1);
+            client.send_message(|address_base| heartbeat_message(address_base, heartbeat_counter));
+            client.update_peer_state(&heartbeat, now);
+            if heartbeat.resync_after > 0.0 && now - last_activity >= heartbeat.resync_after as f64 {
+                nih_trace!("Idle for {} s, resending full state", now - last_activity);
+                send_full_state(&mut client, &mut smoothers, &mut scheduled, &params);
+                last_activity = now;
+            }
+            next_heartbeat = now + heartbeat.interval as f64;
+            continue;
+        }
+        if let Some((send_at, _)) = scheduled.front() {
+            if *send_at <= now {
+                if let Some((send_at, params)) = scheduled.pop_front() {
+                    let params = smoothers.retarget(params, now);
+                    if !params.is_empty() {
+                        client.send_block(ntp_from_unix_secs(send_at), &params);
+                    }
+                }
+                continue;
+            }
+        }
+        if let (Some(next), Some(hz)) = (next_output, output_rate.hz()) {
+            if next <= now {
+                //Keep moving what is already moving, then pick up new changes from process()
+                let mut frame = match smoothers.next_frame() {
+                    Some(_) => smoothers.tick(now),
+                    None => Vec::new(),
+                };
+                let changes = snapshot.changes(|id| smoothers.target(id));
+                frame.extend(smoothers.retarget(changes, now));
+                if !frame.is_empty() {
+                    client.send_block(OSC_TIME_IMMEDIATELY, &frame);
+                    last_activity = now;
+                }
+                //Stay on the grid unless we fell behind
+                next_output = Some((next + 1.0 / hz).max(now));
+                continue;
+            }
+        } else if smoothers.next_frame().map_or(false, |next_frame| next_frame <= now) {
+            let frame = smoothers.tick(now);
+            if !frame.is_empty() {
+                client.send_block(OSC_TIME_IMMEDIATELY, &frame);
+            }
+            continue;
+        }
+
+        //Sleep until the next output frame, scheduled block, smoothing frame, heartbeat or TCP
+        //connect check, whichever comes first
+        let smoothing_frame = match next_output {
+            Some(_) => None,
+            None => smoothers.next_frame(),
+        };
+        let deadline = [
+            next_output,
+            scheduled.front().map(|(send_at, _)| *send_at),
+            smoothing_frame,
+            heartbeat.enabled.then_some(next_heartbeat),
+            client.next_link_poll(now),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(f64::min);
+        let wait = deadline.and_then(|deadline| Duration::try_from_secs_f64((deadline - now).max(0.0)).ok());
+        let channel_message = match wait {
+            Some(wait) => match recv.recv_timeout(wait) {
+                Ok(channel_message) => channel_message,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match recv.recv() {
+                Ok(channel_message) => channel_message,
+                Err(_) => break,
+            },
+        };
+        client.stats.queued.store(recv.len(), Ordering::Relaxed);
+        let send_at = match channel_message {
             OscChannelMessageType::Exit => break,
             OscChannelMessageType::ConnectionChange(message) => {
-                let ip_port = format!("{}:{}", message.ip, message.port);
-                nih_trace!("Connection Change: {}", ip_port);
-                let socket_result = socket.connect(&ip_port);
-                match socket_result {
-                    Ok(_) => connected = true,
-                    Err(e) => {
-                        connected = false;
-                        nih_error!("Failed to connect to {} {:?}", ip_port, e);
-                    }
+                nih_trace!("Connection Change: {}:{}", message.ip, message.port);
+                client.connection_change(message);
+                send_full_state(&mut client, &mut smoothers, &mut scheduled, &params);
+                last_activity = unix_now_secs();
+                continue;
+            }
+            OscChannelMessageType::DestinationsChange(message) => {
+                nih_trace!("Destinations Change: {:?}", message.destinations);
+                client.destinations_change(&message.destinations);
+                continue;
+            }
+            OscChannelMessageType::TransportChange(message) => {
+                nih_trace!(
+                    "Transport Change: {:?} {:?} {:?}",
+                    message.transport,
+                    message.tcp_framing,
+                    message.multicast
+                );
+                client.transport_change(message);
+                continue;
+            }
+            OscChannelMessageType::HeartbeatChange(message) => {
+                nih_trace!("Heartbeat Change: {:?}", message.heartbeat);
+                //The settings come straight from the editor, keep them in range
+                heartbeat = message.heartbeat.clamped();
+                next_heartbeat = unix_now_secs();
+                if !heartbeat.enabled {
+                    client.stats.set_peer_state(PeerState::Unknown);
                 }
                 continue;
             }
             OscChannelMessageType::AddressBaseChange(message) => {
-                address_base = format_osc_address_base(&message.address);
-                nih_trace!("AddressBase Change: {}", address_base);
+                client.address_base = format_osc_address_base(&message.address);
+                nih_trace!("AddressBase Change: {}", client.address_base);
+                client.rebuild_addresses();
                 continue;
             }
-            OscChannelMessageType::Param(message) => OscMessage {
-                addr: format!("{}/param/{}", address_base, message.name),
-                args: vec![OscType::Float(message.value)],
-            },
+            OscChannelMessageType::AddressSchemaChange(message) => {
+                nih_trace!("Address Schema Change: {:?}", message.schema);
+                client.schema = message.schema;
+                client.rebuild_addresses();
+                continue;
+            }
+            OscChannelMessageType::HeadOutputChange(message) => {
+                nih_trace!("Head Output Change: {:?}", message.head_output);
+                client.head_output = message.head_output;
+                continue;
+            }
+            OscChannelMessageType::EnumArgsChange(message) => {
+                nih_trace!("Enum Args Change: {:?}", message.enum_args);
+                client.enum_args = message.enum_args;
+                continue;
+            }
+            OscChannelMessageType::MappingsChange(message) => {
+                nih_trace!("Mappings Change: {:?}", message.mappings);
+                client.mappings = message.mappings;
+                continue;
+            }
+            OscChannelMessageType::SmoothingsChange(message) => {
+                nih_trace!("Smoothings Change: {:?}", message.smoothings);
+                smoothers.settings = message.smoothings;
+                continue;
+            }
+            OscChannelMessageType::OutputRateChange(message) => {
+                nih_trace!("Output Rate Change: {:?}", message.output_rate);
+                output_rate = message.output_rate;
+                next_output = output_rate.hz().map(|_| unix_now_secs());
+                snapshot.set_enabled(next_output.is_some());
+                smoothers.set_frame_rate(output_rate.hz().unwrap_or(SMOOTHING_FRAME_RATE));
+                continue;
+            }
+            OscChannelMessageType::Param(message) => {
+                pending.push(message);
+                continue;
+            }
+            OscChannelMessageType::SendAll => {
+                nih_trace!("Sending full state");
+                send_full_state(&mut client, &mut smoothers, &mut scheduled, &params);
+                last_activity = unix_now_secs();
+                continue;
+            }
+            OscChannelMessageType::BlockEnd(message) => message.send_at,
         };
-        if connected {
-            let packet = OscPacket::Message(osc_message);
-            let buf = match rosc::encoder::encode(&packet) {
-                Ok(buf) => buf,
-                Err(e) => {
-                    nih_error!("Failed to encode osc message {:?}", e);
-                    continue;
+        if pending.is_empty() {
+            continue;
+        }
+        last_activity = unix_now_secs();
+        let params = std::mem::take(&mut pending);
+        match send_at {
+            //Hold timed blocks back until they are due so split blocks go out spaced like the audio
+            Some(send_at) if send_at > unix_now_secs() => {
+                let index = scheduled.partition_point(|(queued_at, _)| *queued_at <= send_at);
+                scheduled.insert(index, (send_at, params));
+            }
+            _ => {
+                let params = smoothers.retarget(params, unix_now_secs());
+                if !params.is_empty() {
+                    let timetag = send_at.map_or(OSC_TIME_IMMEDIATELY, ntp_from_unix_secs);
+                    client.send_block(timetag, &params);
                 }
+            }
+        }
+    }
+}
+
+/// The current value of every param, enums as their variant index
+fn full_state(params: &OsClaPhotonParams) -> Vec<OscParamType> {
+    ParamId::all()
+        .map(|id| OscParamType {
+            id,
+            value: params.osc_value(id),
+        })
+        .collect()
+}
+
+/// Send every param as it is now, smoothing jumps along so it doesnt ramp back. Blocks still
+/// waiting for their timetag hold older values and would roll the receiver back, so they go
+fn send_full_state(
+    client: &mut OscClient,
+    smoothers: &mut Smoothers,
+    scheduled: &mut VecDeque<(f64, Vec<OscParamType>)>,
+    params: &OsClaPhotonParams,
+) {
+    scheduled.clear();
+    let state = full_state(params);
+    smoothers.snap(&state);
+    client.send_block(OSC_TIME_IMMEDIATELY, &state);
+}
+
+/// Everything the worker needs to get packets out to every target
+struct OscClient {
+    socket: UdpSockets,
+    connection: OscConnectionType,
+    transport: OscTransportType,
+    //The main connection is always first, the extra destinations follow
+    targets: Vec<OscTarget>,
+    address_base: String,
+    schema: OscAddressSchema,
+    //Rendered for `address_base`, targets with an override keep their own
+    addresses: AddressTable,
+    head_output: HeadOutput,
+    enum_args: OscEnumArgs,
+    mappings: OscMappings,
+    //Last value sent per param indexed by `ParamId::index()`, grouped heads need the unchanged fields too
+    values: Vec<f32>,
+    stats: Arc<OscStats>,
+    sent: Arc<SentValues>,
+}
+
+impl OscClient {
+    fn new(
+        socket: UdpSockets,
+        connection: OscConnectionType,
+        transport: OscTransportType,
+        destinations: &[OscDestination],
+        address_base: &str,
+        schema: OscAddressSchema,
+        stats: Arc<OscStats>,
+        sent: Arc<SentValues>,
+    ) -> Self {
+        let mut targets = vec![resolve_main_target(&connection, &transport, &stats)];
+        targets.extend(resolve_destinations(destinations, &stats));
+        let address_base = format_osc_address_base(address_base);
+        let addresses = AddressTable::new(&schema, &address_base);
+        let mut client = Self {
+            socket,
+            connection,
+            transport,
+            targets,
+            address_base,
+            schema,
+            addresses,
+            head_output: HeadOutput::default(),
+            enum_args: OscEnumArgs::default(),
+            mappings: OscMappings::default(),
+            values: vec![0.0; ParamId::all().count()],
+            stats,
+            sent,
+        };
+        client.rebuild_addresses();
+        client
+    }
+
+    /// Render every address again after the base, the schema or the targets changed
+    fn rebuild_addresses(&mut self) {
+        self.addresses = AddressTable::new(&self.schema, &self.address_base);
+        for target in &mut self.targets {
+            target.addresses = target
+                .address_base
+                .as_deref()
+                .map(|address_base| AddressTable::new(&self.schema, address_base));
+        }
+    }
+
+    fn connection_change(&mut self, connection: OscConnectionType) {
+        self.connection = connection;
+        self.targets[0] = resolve_main_target(&self.connection, &self.transport, &self.stats);
+    }
+
+    fn destinations_change(&mut self, destinations: &[OscDestination]) {
+        self.targets.truncate(1);
+        self.targets
+            .extend(resolve_destinations(destinations, &self.stats));
+        self.rebuild_addresses();
+    }
+
+    fn transport_change(&mut self, transport: OscTransportType) {
+        self.socket.configure(transport.transport, &transport.multicast);
+        //Existing TCP streams might use the old framing, start over
+        for link in self.targets.iter_mut().filter_map(|target| target.link.as_mut()) {
+            link.disconnect();
+        }
+        self.transport = transport;
+        self.targets[0] = resolve_main_target(&self.connection, &self.transport, &self.stats);
+    }
+
+    /// Fan one block out to every target as a bundle, each with its own address base
+    fn send_block(&mut self, timetag: OscTime, params: &[OscParamType]) {
+        for param in params {
+            if let Some(value) = self.values.get_mut(param.id.index()) {
+                *value = param.value;
+            }
+            self.sent.record(
+                param.id,
+                echo_value(param.id, param.value, self.head_output, &self.mappings),
+            );
+        }
+        let tcp = self.transport.transport == OscTransport::Tcp;
+        let encoding = BlockEncoding {
+            head_output: self.head_output,
+            enum_args: &self.enum_args,
+            mappings: &self.mappings,
+            values: &self.values,
+        };
+        for target in &mut self.targets {
+            let link = match &mut target.link {
+                Some(link) => link,
+                None => continue,
             };
-            let len = match socket.send(&buf[..]) {
-                Ok(buf) => buf,
-                Err(e) => {
-                    nih_error!("Failed to send osc message {:?}", e);
-                    continue;
+            if tcp && !link.is_connected() {
+                link.poll_connect();
+                target.missed(params);
+                continue;
+            }
+            let packet = block_packet(
+                target.address_base.as_deref().unwrap_or(&self.address_base),
+                target.addresses.as_ref().unwrap_or(&self.addresses),
+                &encoding,
+                timetag,
+                params,
+            );
+            let sent = send_osc_packet(&self.socket, &self.transport, link, &packet, &self.stats);
+            if tcp && !sent {
+                target.missed(params);
+            }
+        }
+    }
+
+    /// TCP only, drive the connects of the links that are down. A link that comes back gets the
+    /// cues it missed in order and then the last value of every param
+    fn poll_links(&mut self) {
+        if self.transport.transport != OscTransport::Tcp {
+            return;
+        }
+        let encoding = BlockEncoding {
+            head_output: self.head_output,
+            enum_args: &self.enum_args,
+            mappings: &self.mappings,
+            values: &self.values,
+        };
+        for target in &mut self.targets {
+            let link = match &mut target.link {
+                Some(link) => link,
+                None => continue,
+            };
+            if !link.poll_connect() || !target.stale {
+                continue;
+            }
+            let base = target.address_base.as_deref().unwrap_or(&self.address_base);
+            let addresses = target.addresses.as_ref().unwrap_or(&self.addresses);
+            let cues = std::mem::take(&mut target.missed_cues);
+            let state: Vec<OscParamType> = ParamId::all()
+                .map(|id| OscParamType {
+                    id,
+                    value: self.values[id.index()],
+                })
+                .collect();
+            nih_log!("TCP link to {} is back, sending {} missed cue(s) and the state", link.addr(), cues.len());
+            let mut sent = true;
+            for block in [cues, state] {
+                if sent && !block.is_empty() {
+                    let packet = block_packet(base, addresses, &encoding, OSC_TIME_IMMEDIATELY, &block);
+                    sent = send_osc_packet(&self.socket, &self.transport, link, &packet, &self.stats);
                 }
+            }
+            target.stale = !sent;
+        }
+    }
+
+    /// When the worker should call `poll_links()` again, None if every link is fine
+    fn next_link_poll(&self, now: f64) -> Option<f64> {
+        let pending = self.transport.transport == OscTransport::Tcp
+            && self
+                .targets
+                .iter()
+                .any(|target| target.stale && target.link.as_ref().map_or(false, |link| !link.is_connected()));
+        pending.then_some(now + TCP_POLL_INTERVAL)
+    }
+
+    /// Send a single message to every target, built with that target's address base. TCP links
+    /// that are down skip it, nothing sent this way has to be caught up on
+    fn send_message(&mut self, message: impl Fn(&str) -> OscMessage) {
+        let tcp = self.transport.transport == OscTransport::Tcp;
+        for target in &mut self.targets {
+            let link = match &mut target.link {
+                Some(link) => link,
+                None => continue,
             };
-            if len != buf.len() {
-                nih_trace!("UDP packet not fully sent");
+            if tcp && !link.is_connected() {
+                link.poll_connect();
+                continue;
             }
-            nih_trace!("Sent {:?} packet", packet);
+            let base = target.address_base.as_deref().unwrap_or(&self.address_base);
+            let packet = OscPacket::Message(message(base));
+            send_osc_packet(&self.socket, &self.transport, link, &packet, &self.stats);
+        }
+    }
+
+    /// Flip the peer state based on how long ago each destination acked a heartbeat. Acks are
+    /// matched by the address base the destination is sent with
+    fn update_peer_state(&self, heartbeat: &OscHeartbeat, now: f64) {
+        let targets: Vec<(&OscLink, &str)> = self
+            .targets
+            .iter()
+            .filter_map(|target| {
+                let base = target.address_base.as_deref().unwrap_or(&self.address_base);
+                Some((target.link.as_ref()?, base))
+            })
+            .collect();
+        self.stats.watch_acks(targets.iter().map(|(_, base)| *base));
+        let peers: Vec<PeerStatus> = targets
+            .iter()
+            .map(|(link, base)| {
+                let last_ack = self.stats.last_ack(base);
+                PeerStatus {
+                    name: link.addr().to_string(),
+                    online: last_ack.map_or(false, |last_ack| now - last_ack <= heartbeat.timeout as f64),
+                    last_ack,
+                }
+            })
+            .collect();
+        let peer_state = if !self.stats.is_listening() {
+            PeerState::NoListener
+        } else if !peers.is_empty() && peers.iter().all(|peer| peer.online) {
+            PeerState::Online
+        } else {
+            PeerState::Offline
+        };
+        self.stats.set_peers(peers);
+        let previous = self.stats.set_peer_state(peer_state);
+        if previous != peer_state {
+            nih_log!("Peer is now {:?}", peer_state);
+        }
+    }
+}
+
+
+/// How the params of a block are turned into messages
+struct BlockEncoding<'a> {
+    head_output: HeadOutput,
+    enum_args: &'a OscEnumArgs,
+    mappings: &'a OscMappings,
+    //Last value sent per param, grouped heads need the unchanged fields too
+    values: &'a [f32],
+}
+
+/// One block as a bundle for a target with the given address base and addresses
+fn block_packet(
+    address_base: &str,
+    addresses: &AddressTable,
+    encoding: &BlockEncoding,
+    timetag: OscTime,
+    params: &[OscParamType],
+) -> OscPacket {
+    //Heads touched by this block, each goes out once with all of its fields
+    let mut heads: Vec<usize> = Vec::new();
+    if encoding.head_output != HeadOutput::Separate {
+        for param in params {
+            if let ParamId::Head(index, _) = param.id {
+                if !heads.contains(&index) {
+                    heads.push(index);
+                }
+            }
+        }
+    }
+    let mut content: Vec<OscPacket> = params
+        .iter()
+        .filter(|param| {
+            encoding.head_output == HeadOutput::Separate || !matches!(param.id, ParamId::Head(..))
+        })
+        .map(|param| {
+            OscPacket::Message(param_message(
+                addresses.address(param.id),
+                param.id,
+                param.value,
+                encoding.enum_args,
+                encoding.mappings,
+            ))
+        })
+        .collect();
+    content.extend(heads.iter().map(|index| {
+        OscPacket::Message(head_message(
+            address_base,
+            *index,
+            encoding.values,
+            encoding.head_output,
+            encoding.mappings,
+        ))
+    }));
+    OscPacket::Bundle(OscBundle { timetag, content })
+}
+
+/// Returns false if the packet didn't get out
+fn send_osc_packet(
+    socket: &UdpSockets,
+    transport: &OscTransportType,
+    link: &mut OscLink,
+    packet: &OscPacket,
+    stats: &OscStats,
+) -> bool {
+    let buf = match rosc::encoder::encode(packet) {
+        Ok(buf) => buf,
+        Err(e) => {
+            nih_error!("Failed to encode osc message {:?}", e);
+            stats.record_error(format!("Failed to encode osc message {:?}", e));
+            return false;
+        }
+    };
+    let len = match link.send(socket, transport.transport, transport.tcp_framing, &buf[..]) {
+        Ok(buf) => buf,
+        Err(e) => {
+            nih_error!("Failed to send osc message to {} {:?}", link.addr(), e);
+            stats.record_error(format!("Failed to send to {} {}", link.addr(), e));
+            return false;
         }
+    };
+    stats.record_sent(len);
+    if len != buf.len() {
+        nih_trace!("UDP packet not fully sent");
     }
+    nih_trace!("Sent {:?} packet to {}", packet, link.addr());
+    true
 }
 
-fn format_osc_address_base(raw_base: &str) -> String {
+pub(crate) fn format_osc_address_base(raw_base: &str) -> String {
     if raw_base.is_empty() {
         return "".to_string();
     } else {
@@ -775,7 +2029,7 @@ fn format_osc_address_base(raw_base: &str) -> String {
 }
 
 impl ClapPlugin for OsClaPhoton {
-    const CLAP_ID: &'static str = "xyz.vanta.osclaphoton";
+    const CLAP_ID: &'static str = PLUGIN_VARIANT.1;
     const CLAP_DESCRIPTION: Option<&'static str> =
         Some("Outputs OSC Photon control from the DAW");
     const CLAP_FEATURES: &'static [ClapFeature] = &[
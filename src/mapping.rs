@@ -0,0 +1,247 @@
+use rosc::OscType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::address::ParamId;
+
+/// Power used by the exponential curve
+const EXP_CURVE_POWER: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum MappingCurve {
+    #[default]
+    Linear,
+    /// Slow start, more resolution at the low end
+    Exponential,
+    /// Smoothstep, eases in and out
+    SCurve,
+}
+
+impl MappingCurve {
+    pub fn name(self) -> &'static str {
+        match self {
+            MappingCurve::Linear => "Linear",
+            MappingCurve::Exponential => "Exp",
+            MappingCurve::SCurve => "S-Curve",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            MappingCurve::Linear => MappingCurve::Exponential,
+            MappingCurve::Exponential => MappingCurve::SCurve,
+            MappingCurve::SCurve => MappingCurve::Linear,
+        }
+    }
+
+    pub fn apply(self, x: f32) -> f32 {
+        match self {
+            MappingCurve::Linear => x,
+            MappingCurve::Exponential => x.powf(EXP_CURVE_POWER),
+            MappingCurve::SCurve => x * x * (3.0 - 2.0 * x),
+        }
+    }
+
+    fn inverse(self, y: f32) -> f32 {
+        match self {
+            MappingCurve::Linear => y,
+            MappingCurve::Exponential => y.powf(1.0 / EXP_CURVE_POWER),
+            //Closed form inverse of smoothstep
+            MappingCurve::SCurve => 0.5 - ((1.0 - 2.0 * y).asin() / 3.0).sin(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum MappingOutput {
+    #[default]
+    Float,
+    /// Rounded to the nearest int
+    Int,
+}
+
+impl MappingOutput {
+    pub fn name(self) -> &'static str {
+        match self {
+            MappingOutput::Float => "Float",
+            MappingOutput::Int => "Int",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            MappingOutput::Float => MappingOutput::Int,
+            MappingOutput::Int => MappingOutput::Float,
+        }
+    }
+}
+
+/// How a float param's 0-1 value is scaled before it goes out, e.g. to 0-255 for DMX like
+/// receivers or to degrees
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OscMapping {
+    pub min: f32,
+    pub max: f32,
+    pub invert: bool,
+    pub curve: MappingCurve,
+    /// Quantize to this many steps across the range, 0 turns it off
+    pub steps: u32,
+    pub output: MappingOutput,
+}
+
+impl Default for OscMapping {
+    fn default() -> Self {
+        Self {
+            min: 0.0,
+            max: 1.0,
+            invert: false,
+            curve: MappingCurve::Linear,
+            steps: 0,
+            output: MappingOutput::Float,
+        }
+    }
+}
+
+impl OscMapping {
+    /// Normalized value to output value: invert, curve, quantize, then scale to min-max
+    pub fn map(&self, value: f32) -> f32 {
+        let mut x = value.clamp(0.0, 1.0);
+        if self.invert {
+            x = 1.0 - x;
+        }
+        x = self.curve.apply(x);
+        if self.steps > 0 {
+            x = (x * self.steps as f32).round() / self.steps as f32;
+        }
+        self.min + x * (self.max - self.min)
+    }
+
+    /// Output value back to the normalized value, for feedback
+    pub fn unmap(&self, value: f32) -> f32 {
+        let range = self.max - self.min;
+        if range == 0.0 {
+            return 0.0;
+        }
+        let mut x = self.curve.inverse(((value - self.min) / range).clamp(0.0, 1.0));
+        if self.invert {
+            x = 1.0 - x;
+        }
+        x.clamp(0.0, 1.0)
+    }
+}
+
+/// Mappings for the params that have one, keyed by host param id, e.g. `beamNr` or `tilt_3`.
+/// Params without an entry are sent as is.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OscMappings {
+    pub params: HashMap<String, OscMapping>,
+}
+
+impl OscMappings {
+    /// Enums have no mapping, they always send the variant index
+    pub fn get(&self, id: ParamId) -> Option<&OscMapping> {
+        if id.is_enum() {
+            return None;
+        }
+        self.params.get(&id.host_id())
+    }
+
+    /// The OSC argument for a value
+    pub fn arg(&self, id: ParamId, value: f32) -> OscType {
+        match self.get(id) {
+            Some(mapping) => match mapping.output {
+                MappingOutput::Float => OscType::Float(mapping.map(value)),
+                MappingOutput::Int => OscType::Int(mapping.map(value).round() as i32),
+            },
+            None => OscType::Float(value),
+        }
+    }
+
+    pub fn unmap(&self, id: ParamId, value: f32) -> f32 {
+        match self.get(id) {
+            Some(mapping) => mapping.unmap(value),
+            None => value,
+        }
+    }
+
+    /// What feedback reads back when a receiver echoes `arg(id, value)` unchanged. Quantizing,
+    /// ints and the curves don't round trip, so this is not always `value`
+    pub fn echo(&self, id: ParamId, value: f32) -> f32 {
+        match self.arg(id, value) {
+            OscType::Float(arg) => self.unmap(id, arg),
+            OscType::Int(arg) => self.unmap(id, arg as f32),
+            _ => value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [MappingCurve; 3] = [MappingCurve::Linear, MappingCurve::Exponential, MappingCurve::SCurve];
+
+    #[test]
+    fn curves_round_trip_through_their_inverse() {
+        for curve in CURVES {
+            for step in 0..=20 {
+                let x = step as f32 / 20.0;
+                let y = curve.inverse(curve.apply(x));
+                assert!((x - y).abs() < 1e-4, "{:?} {} came back as {}", curve, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn mapping_round_trips_with_range_and_invert() {
+        for curve in CURVES {
+            for invert in [false, true] {
+                let mapping = OscMapping {
+                    min: 10.0,
+                    max: -245.0,
+                    invert,
+                    curve,
+                    ..OscMapping::default()
+                };
+                for step in 0..=20 {
+                    let value = step as f32 / 20.0;
+                    let echoed = mapping.unmap(mapping.map(value));
+                    assert!((value - echoed).abs() < 1e-3, "{:?} {} came back as {}", mapping, value, echoed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_feedback_is_clamped() {
+        let mapping = OscMapping {
+            max: 255.0,
+            ..OscMapping::default()
+        };
+        assert_eq!(mapping.unmap(300.0), 1.0);
+        assert_eq!(mapping.unmap(-5.0), 0.0);
+        let flat = OscMapping {
+            min: 1.0,
+            max: 1.0,
+            ..OscMapping::default()
+        };
+        assert_eq!(flat.unmap(1.0), 0.0);
+    }
+
+    #[test]
+    fn int_output_echoes_the_rounded_value() {
+        let mut mappings = OscMappings::default();
+        mappings.params.insert(
+            "zoom".to_string(),
+            OscMapping {
+                max: 10.0,
+                output: MappingOutput::Int,
+                ..OscMapping::default()
+            },
+        );
+        assert_eq!(mappings.arg(ParamId::Zoom, 0.44), OscType::Int(4));
+        assert!((mappings.echo(ParamId::Zoom, 0.44) - 0.4).abs() < 1e-6);
+        //No mapping, sent and read back as is
+        assert_eq!(mappings.echo(ParamId::Offset, 0.44), 0.44);
+    }
+}
@@ -0,0 +1,508 @@
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::address::ParamId;
+use crate::enum_variant_names;
+use crate::mapping::MappingCurve;
+use crate::overrides::ParamOverrides;
+
+/// CC numbers with a special meaning, see the MIDI 1.0 spec
+const CC_DATA_ENTRY_MSB: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_NRPN_LSB: u8 = 98;
+const CC_NRPN_MSB: u8 = 99;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+
+/// How often the editor checks whether process() has seen a control to learn
+const LEARN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// A 14-bit fader sends its MSB and LSB a moment apart, wait for the rest before picking the kind
+const LEARN_SETTLE_TIME: Duration = Duration::from_millis(150);
+
+/// In order of preference when learning, a control that sends more than one of these is
+/// learned as the last one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MidiControlKind {
+    Cc,
+    /// CC 0-31 with its LSB on CC 32-63
+    Cc14,
+    Nrpn,
+}
+
+impl MidiControlKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            MidiControlKind::Cc => "CC",
+            MidiControlKind::Cc14 => "CC 14-bit",
+            MidiControlKind::Nrpn => "NRPN",
+        }
+    }
+
+    /// Cycles through every kind and off
+    pub fn next(kind: Option<Self>) -> Option<Self> {
+        match kind {
+            None => Some(MidiControlKind::Cc),
+            Some(MidiControlKind::Cc) => Some(MidiControlKind::Cc14),
+            Some(MidiControlKind::Cc14) => Some(MidiControlKind::Nrpn),
+            Some(MidiControlKind::Nrpn) => None,
+        }
+    }
+
+    /// Highest number a control of this kind can have
+    pub fn max_number(self) -> u16 {
+        match self {
+            MidiControlKind::Cc => 127,
+            MidiControlKind::Cc14 => 31,
+            MidiControlKind::Nrpn => 16383,
+        }
+    }
+
+    fn resolution(self) -> u16 {
+        match self {
+            MidiControlKind::Cc => 127,
+            MidiControlKind::Cc14 | MidiControlKind::Nrpn => 16383,
+        }
+    }
+}
+
+/// One hardware control, matched by kind, channel and number
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MidiControl {
+    pub kind: MidiControlKind,
+    /// 0-15
+    pub channel: u8,
+    /// CC number, the MSB CC number for 14-bit pairs or the 14-bit NRPN number
+    pub number: u16,
+}
+
+impl MidiControl {
+    pub fn name(&self) -> String {
+        match self.kind {
+            MidiControlKind::Cc => format!("CC {} ch {}", self.number, self.channel + 1),
+            MidiControlKind::Cc14 => format!(
+                "CC {}/{} ch {}",
+                self.number,
+                self.number + 32,
+                self.channel + 1
+            ),
+            MidiControlKind::Nrpn => format!("NRPN {} ch {}", self.number, self.channel + 1),
+        }
+    }
+
+    /// Packed for an atomic, never 0 so 0 can mean none
+    fn to_bits(self) -> u32 {
+        let kind = match self.kind {
+            MidiControlKind::Cc => 1,
+            MidiControlKind::Cc14 => 2,
+            MidiControlKind::Nrpn => 3,
+        };
+        kind << 24 | (self.channel as u32) << 16 | self.number as u32
+    }
+
+    fn from_bits(bits: u32) -> Option<Self> {
+        let kind = match bits >> 24 {
+            1 => MidiControlKind::Cc,
+            2 => MidiControlKind::Cc14,
+            3 => MidiControlKind::Nrpn,
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            channel: (bits >> 16 & 0xFF) as u8,
+            number: (bits & 0xFFFF) as u16,
+        })
+    }
+}
+
+/// A control driving a param. The control's 0-1 value goes through the curve and is scaled to
+/// min-max in param units, enums take the variant index
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MidiBinding {
+    pub control: MidiControl,
+    pub min: f32,
+    pub max: f32,
+    pub curve: MappingCurve,
+}
+
+impl MidiBinding {
+    /// Full range of the param
+    pub fn new(id: ParamId, control: MidiControl) -> Self {
+        let max = match enum_variant_names(id).len() {
+            0 => 1.0,
+            variants => (variants - 1) as f32,
+        };
+        Self {
+            control,
+            min: 0.0,
+            max,
+            curve: MappingCurve::Linear,
+        }
+    }
+
+    /// Kept inside the param's range, the variants of an enum or 0-1
+    fn value(&self, id: ParamId, x: f32) -> f32 {
+        let value = self.min + (self.max - self.min) * self.curve.apply(x.clamp(0.0, 1.0));
+        match enum_variant_names(id).len() {
+            0 => value.clamp(0.0, 1.0),
+            variants => value.round().clamp(0.0, (variants - 1) as f32),
+        }
+    }
+}
+
+/// MIDI bindings by host param id, at most one control per param
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MidiBindings {
+    pub params: HashMap<String, MidiBinding>,
+}
+
+/// Controls params are mirrored to on the MIDI output, by host param id
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MidiOutputs {
+    pub params: HashMap<String, MidiControl>,
+}
+
+/// A binding resolved to its param so process() never has to look up or allocate
+struct MidiRoute {
+    id: ParamId,
+    binding: MidiBinding,
+    /// Latest 0-1 value of the control as f32 bits
+    x: AtomicU32,
+    /// Set by every control change until `render()` plays it
+    moved: AtomicBool,
+}
+
+/// A mirrored param and the last value sent for it, u32::MAX before the first one
+struct MidiOutRoute {
+    id: ParamId,
+    control: MidiControl,
+    last: AtomicU32,
+}
+
+/// Shared between the editor, which learns and edits bindings, and process(), which applies them
+#[derive(Default)]
+pub struct MidiShared {
+    /// Index into `ParamId::all()` plus one of the param being learned, 0 when not learning
+    learn_target: AtomicUsize,
+    /// Best control process() has seen while learning, packed, 0 when none yet
+    learned: AtomicU32,
+    routes: RwLock<Vec<MidiRoute>>,
+    outputs: RwLock<Vec<MidiOutRoute>>,
+}
+
+impl MidiShared {
+    /// Rebuild the routes process() uses. A param that stays bound keeps what it plays until its
+    /// control moves again, an unbound one goes back to its own value on the next block
+    pub fn set_bindings(&self, bindings: &MidiBindings) {
+        let routes = ParamId::all()
+            .filter_map(|id| {
+                bindings.params.get(&id.host_id()).map(|binding| MidiRoute {
+                    id,
+                    binding: binding.clone(),
+                    x: AtomicU32::new(0),
+                    moved: AtomicBool::new(false),
+                })
+            })
+            .collect();
+        *self.routes.write() = routes;
+    }
+
+    /// Rebuild the mirrored params, everything is sent again on the next block
+    pub fn set_outputs(&self, outputs: &MidiOutputs) {
+        let routes = ParamId::all()
+            .filter_map(|id| {
+                outputs.params.get(&id.host_id()).map(|control| MidiOutRoute {
+                    id,
+                    control: *control,
+                    last: AtomicU32::new(u32::MAX),
+                })
+            })
+            .collect();
+        *self.outputs.write() = routes;
+    }
+
+    /// Safe to call from process(). Calls `send` with channel, CC number and 7-bit value for every
+    /// CC needed to mirror the params that changed since the last call. `value` gives a param's
+    /// current value as we send it over OSC
+    pub fn mirror(&self, value: impl Fn(ParamId) -> f32, mut send: impl FnMut(u8, u8, u8)) {
+        let Some(outputs) = self.outputs.try_read() else {
+            return;
+        };
+        for route in outputs.iter() {
+            let resolution = route.control.kind.resolution();
+            let x = normalized(route.id, value(route.id));
+            let scaled = (x * resolution as f32).round() as u32;
+            if route.last.swap(scaled, Ordering::Relaxed) != scaled {
+                control_messages(route.control, scaled as u16, &mut send);
+            }
+        }
+    }
+
+    pub fn start_learn(&self, param: usize) {
+        self.learned.store(0, Ordering::Relaxed);
+        self.learn_target.store(param + 1, Ordering::Release);
+    }
+
+    pub fn stop_learn(&self) {
+        self.learn_target.store(0, Ordering::Release);
+    }
+
+    pub fn is_learning(&self) -> bool {
+        self.learn_target.load(Ordering::Acquire) != 0
+    }
+
+    /// Blocks until process() has seen a control or learning is stopped, run it off the GUI
+    /// thread. Returns the param index and the control
+    pub fn wait_for_learn(&self) -> Option<(usize, MidiControl)> {
+        while self.is_learning() {
+            thread::sleep(LEARN_POLL_INTERVAL);
+            if self.learned.load(Ordering::Relaxed) != 0 {
+                thread::sleep(LEARN_SETTLE_TIME);
+                let target = self.learn_target.swap(0, Ordering::AcqRel);
+                let learned = MidiControl::from_bits(self.learned.swap(0, Ordering::Relaxed));
+                return match (target, learned) {
+                    (0, _) | (_, None) => None,
+                    (target, Some(control)) => Some((target - 1, control)),
+                };
+            }
+        }
+        None
+    }
+
+    /// Safe to call from process(). Learns the control or keeps its value for every param bound
+    /// to it until `render()`. The routes are skipped for this event if the editor is rebuilding
+    /// them
+    pub fn handle(&self, control: MidiControl, x: f32) {
+        if self.is_learning() {
+            //Keep the most specific kind, e.g. an NRPN over the CCs that carry it
+            let bits = control.to_bits();
+            let _ = self.learned.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |learned| {
+                (bits >> 24 >= learned >> 24).then_some(bits)
+            });
+            return;
+        }
+        let Some(routes) = self.routes.try_read() else {
+            return;
+        };
+        for route in routes.iter().filter(|route| route.binding.control == control) {
+            route.x.store(x.to_bits(), Ordering::Relaxed);
+            route.moved.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Safe to call from process(). Plays every control that moved since the last call on the
+    /// params bound to it and clears the params that aren't bound anymore, calls `on_change` for
+    /// those. The overrides let go of a param on their own once the host moves it
+    pub fn render(&self, overrides: &ParamOverrides, mut on_change: impl FnMut(ParamId)) {
+        let Some(routes) = self.routes.try_read() else {
+            return;
+        };
+        //Routes are in `ParamId::all()` order with at most one per param
+        let mut routes = routes.iter().peekable();
+        for id in ParamId::all() {
+            match routes.next_if(|route| route.id == id) {
+                Some(route) => {
+                    if route.moved.swap(false, Ordering::Relaxed) {
+                        let x = f32::from_bits(route.x.load(Ordering::Relaxed));
+                        overrides.set(id, route.binding.value(id, x));
+                    }
+                }
+                None => {
+                    if overrides.clear(id) {
+                        on_change(id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Turns CC events into controls. Keeps the running MSB and NRPN state per channel in fixed
+/// arrays so process() never allocates
+pub struct MidiDecoder {
+    /// Last MSB of CC 0-31
+    msb: [[u8; 32]; 16],
+    /// Bit per CC 0-31 that has been seen followed right away by its LSB. Until then CC 32-63
+    /// count as plain CCs, plenty of controllers use them for knobs of their own
+    pairs: [u32; 16],
+    last_cc: [u8; 16],
+    /// Selected NRPN number, None after an RPN was selected
+    nrpn: [Option<u16>; 16],
+    nrpn_msb: [u8; 16],
+    data_msb: [u8; 16],
+}
+
+impl Default for MidiDecoder {
+    fn default() -> Self {
+        Self {
+            msb: [[0; 32]; 16],
+            pairs: [0; 16],
+            last_cc: [u8::MAX; 16],
+            nrpn: [None; 16],
+            nrpn_msb: [0; 16],
+            data_msb: [0; 16],
+        }
+    }
+}
+
+impl MidiDecoder {
+    /// `value` is the raw 7-bit CC value. Calls `on_control` with every control this event
+    /// moves and its 0-1 value, a CC can be a plain CC and half of a 14-bit pair at once
+    pub fn decode(&mut self, channel: u8, cc: u8, value: u8, mut on_control: impl FnMut(MidiControl, f32)) {
+        let ch = channel as usize & 0x0F;
+        let control = |kind, number| MidiControl {
+            kind,
+            channel: channel & 0x0F,
+            number,
+        };
+        on_control(control(MidiControlKind::Cc, cc as u16), value as f32 / 127.0);
+        let last_cc = std::mem::replace(&mut self.last_cc[ch], cc);
+        match cc {
+            CC_NRPN_MSB => {
+                self.nrpn_msb[ch] = value;
+                self.nrpn[ch] = Some((value as u16) << 7);
+            }
+            CC_NRPN_LSB => self.nrpn[ch] = Some((self.nrpn_msb[ch] as u16) << 7 | value as u16),
+            CC_RPN_MSB | CC_RPN_LSB => self.nrpn[ch] = None,
+            CC_DATA_ENTRY_MSB => {
+                self.data_msb[ch] = value;
+                if let Some(number) = self.nrpn[ch] {
+                    on_control(control(MidiControlKind::Nrpn, number), fourteen_bit(value, 0));
+                }
+            }
+            CC_DATA_ENTRY_LSB => {
+                if let Some(number) = self.nrpn[ch] {
+                    on_control(
+                        control(MidiControlKind::Nrpn, number),
+                        fourteen_bit(self.data_msb[ch], value),
+                    );
+                }
+            }
+            0..=31 => {
+                self.msb[ch][cc as usize] = value;
+                //A new MSB resets the LSB
+                if self.pairs[ch] & 1 << cc != 0 {
+                    on_control(control(MidiControlKind::Cc14, cc as u16), fourteen_bit(value, 0));
+                }
+            }
+            32..=63 => {
+                let number = cc - 32;
+                if last_cc == number {
+                    self.pairs[ch] |= 1 << number;
+                }
+                if self.pairs[ch] & 1 << number != 0 {
+                    on_control(
+                        control(MidiControlKind::Cc14, number as u16),
+                        fourteen_bit(self.msb[ch][number as usize], value),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 0-1 across the param's range, enums spread their variants over it
+fn normalized(id: ParamId, value: f32) -> f32 {
+    match enum_variant_names(id).len() {
+        0 => value.clamp(0.0, 1.0),
+        1 => 0.0,
+        variants => (value / (variants - 1) as f32).clamp(0.0, 1.0),
+    }
+}
+
+/// The CCs that carry `value`, a 7-bit value for plain CCs and 14-bit otherwise
+fn control_messages(control: MidiControl, value: u16, send: &mut impl FnMut(u8, u8, u8)) {
+    let channel = control.channel & 0x0F;
+    let msb = (value >> 7 & 0x7F) as u8;
+    let lsb = (value & 0x7F) as u8;
+    match control.kind {
+        MidiControlKind::Cc => send(channel, control.number as u8 & 0x7F, (value & 0x7F) as u8),
+        MidiControlKind::Cc14 => {
+            let number = control.number as u8 & 0x1F;
+            send(channel, number, msb);
+            send(channel, number + 32, lsb);
+        }
+        MidiControlKind::Nrpn => {
+            send(channel, CC_NRPN_MSB, (control.number >> 7 & 0x7F) as u8);
+            send(channel, CC_NRPN_LSB, (control.number & 0x7F) as u8);
+            send(channel, CC_DATA_ENTRY_MSB, msb);
+            send(channel, CC_DATA_ENTRY_LSB, lsb);
+        }
+    }
+}
+
+fn fourteen_bit(msb: u8, lsb: u8) -> f32 {
+    ((msb as u16) << 7 | lsb as u16) as f32 / 16383.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The controls a CC moves besides the plain CC itself
+    fn decode(decoder: &mut MidiDecoder, channel: u8, cc: u8, value: u8) -> Vec<(MidiControl, f32)> {
+        let mut controls = Vec::new();
+        decoder.decode(channel, cc, value, |control, value| {
+            if control.kind != MidiControlKind::Cc {
+                controls.push((control, value));
+            }
+        });
+        controls
+    }
+
+    fn control(kind: MidiControlKind, channel: u8, number: u16) -> MidiControl {
+        MidiControl { kind, channel, number }
+    }
+
+    #[test]
+    fn cc_pair_becomes_14_bit_once_the_lsb_follows_its_msb() {
+        let mut decoder = MidiDecoder::default();
+        assert!(decode(&mut decoder, 0, 7, 64).is_empty());
+        assert_eq!(
+            decode(&mut decoder, 0, 39, 1),
+            vec![(control(MidiControlKind::Cc14, 0, 7), fourteen_bit(64, 1))]
+        );
+        //Known pair, a new MSB resets the LSB
+        assert_eq!(
+            decode(&mut decoder, 0, 7, 127),
+            vec![(control(MidiControlKind::Cc14, 0, 7), fourteen_bit(127, 0))]
+        );
+        //Other channels keep their own pairs
+        assert!(decode(&mut decoder, 1, 7, 64).is_empty());
+    }
+
+    #[test]
+    fn lsb_without_its_msb_stays_a_plain_cc() {
+        let mut decoder = MidiDecoder::default();
+        assert!(decode(&mut decoder, 0, 39, 10).is_empty());
+        //Another CC in between doesn't pair them either
+        decode(&mut decoder, 0, 7, 64);
+        decode(&mut decoder, 0, 1, 0);
+        assert!(decode(&mut decoder, 0, 39, 10).is_empty());
+    }
+
+    #[test]
+    fn nrpn_data_entry_moves_the_selected_number() {
+        let mut decoder = MidiDecoder::default();
+        //Data entry before any NRPN is selected does nothing
+        assert!(decode(&mut decoder, 2, CC_DATA_ENTRY_MSB, 64).is_empty());
+        decode(&mut decoder, 2, CC_NRPN_MSB, 1);
+        decode(&mut decoder, 2, CC_NRPN_LSB, 2);
+        let nrpn = control(MidiControlKind::Nrpn, 2, 1 << 7 | 2);
+        assert_eq!(
+            decode(&mut decoder, 2, CC_DATA_ENTRY_MSB, 64),
+            vec![(nrpn, fourteen_bit(64, 0))]
+        );
+        assert_eq!(
+            decode(&mut decoder, 2, CC_DATA_ENTRY_LSB, 5),
+            vec![(nrpn, fourteen_bit(64, 5))]
+        );
+        //Selecting an RPN stops data entry from reaching the NRPN
+        decode(&mut decoder, 2, CC_RPN_MSB, 0);
+        assert!(decode(&mut decoder, 2, CC_DATA_ENTRY_MSB, 10).is_empty());
+    }
+}
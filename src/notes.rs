@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::address::HeadField;
+use crate::NUM_PARAMS;
+
+/// Marks a head field that isn't overridden
+const NO_OVERRIDE: u32 = f32::NAN.to_bits();
+
+/// Which notes play which heads and the envelope every note plays
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NoteMode {
+    pub enabled: bool,
+    /// 0-15
+    pub channel: u8,
+    /// Note that plays head 1, the next note head 2 and so on
+    pub first_note: u8,
+    /// Seconds from 0 to the velocity peak
+    pub attack: f32,
+    /// Seconds the peak is held at least, longer while the note is held
+    pub hold: f32,
+    /// Seconds from the level at release back to 0
+    pub release: f32,
+    /// Colour the head by the note's pitch class, C is red
+    pub hue: bool,
+}
+
+impl Default for NoteMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channel: 0,
+            first_note: 36,
+            attack: 0.01,
+            hold: 0.1,
+            release: 0.5,
+            hue: false,
+        }
+    }
+}
+
+/// Head values the note mode or the analyser plays, one set for each of them. NaN marks a field
+/// that is left to its param, `clear()` hands every head back
+pub struct HeadOverrides {
+    values: [[AtomicU32; HeadField::ALL.len()]; NUM_PARAMS],
+}
+
+impl Default for HeadOverrides {
+    fn default() -> Self {
+        Self {
+            values: core::array::from_fn(|_| core::array::from_fn(|_| AtomicU32::new(NO_OVERRIDE))),
+        }
+    }
+}
+
+impl HeadOverrides {
+    pub fn get(&self, index: usize, field: HeadField) -> Option<f32> {
+        let value = f32::from_bits(self.values.get(index)?[field as usize].load(Ordering::Relaxed));
+        (!value.is_nan()).then_some(value)
+    }
+
+    pub fn set(&self, index: usize, field: HeadField, value: f32) {
+        self.values[index][field as usize].store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for head in &self.values {
+            for value in head {
+                value.store(NO_OVERRIDE, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Stage {
+    #[default]
+    Idle,
+    Attack,
+    Hold,
+    Release,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct HeadEnvelope {
+    stage: Stage,
+    /// Seconds since the stage started
+    time: f32,
+    peak: f32,
+    /// Level the release starts from, a note can be released during its attack
+    release_from: f32,
+    held: bool,
+}
+
+impl HeadEnvelope {
+    fn level(&self, mode: &NoteMode) -> f32 {
+        match self.stage {
+            Stage::Idle => 0.0,
+            Stage::Attack if mode.attack > 0.0 => self.peak * (self.time / mode.attack).min(1.0),
+            Stage::Attack | Stage::Hold => self.peak,
+            Stage::Release if mode.release > 0.0 => {
+                self.release_from * (1.0 - self.time / mode.release).max(0.0)
+            }
+            Stage::Release => 0.0,
+        }
+    }
+
+    fn release(&mut self, mode: &NoteMode) {
+        self.release_from = self.level(mode);
+        self.stage = Stage::Release;
+        self.time = 0.0;
+    }
+
+    fn advance(&mut self, mode: &NoteMode, seconds: f32) {
+        self.time += seconds;
+        match self.stage {
+            Stage::Attack if self.time >= mode.attack => {
+                self.stage = Stage::Hold;
+                self.time -= mode.attack;
+                //Fall through so a short hold can end in the same block
+                self.advance(mode, 0.0);
+            }
+            Stage::Hold if !self.held && self.time >= mode.hold => self.release(mode),
+            Stage::Release if self.time >= mode.release => *self = Self::default(),
+            _ => {}
+        }
+    }
+}
+
+/// Plays heads from notes, one envelope per head that a new note on the same head restarts
+#[derive(Default)]
+pub struct NotePlayer {
+    envelopes: [HeadEnvelope; NUM_PARAMS],
+    //Whether the overrides have to be cleared once the mode is turned off
+    active: bool,
+}
+
+impl NotePlayer {
+    fn head(mode: &NoteMode, note: u8) -> Option<usize> {
+        let head = note.checked_sub(mode.first_note)? as usize;
+        (head < NUM_PARAMS).then_some(head)
+    }
+
+    /// `velocity` is 0-1 and becomes the dimmer peak
+    pub fn note_on(&mut self, mode: &NoteMode, note: u8, velocity: f32, overrides: &HeadOverrides) {
+        let Some(head) = Self::head(mode, note) else {
+            return;
+        };
+        self.envelopes[head] = HeadEnvelope {
+            stage: Stage::Attack,
+            time: 0.0,
+            peak: velocity.clamp(0.0, 1.0),
+            release_from: 0.0,
+            held: true,
+        };
+        if mode.hue {
+            let [red, green, blue] = hue_to_rgb((note % 12) as f32 / 12.0);
+            overrides.set(head, HeadField::Red, red);
+            overrides.set(head, HeadField::Green, green);
+            overrides.set(head, HeadField::Blue, blue);
+        }
+    }
+
+    pub fn note_off(&mut self, mode: &NoteMode, note: u8) {
+        if let Some(head) = Self::head(mode, note) {
+            let envelope = &mut self.envelopes[head];
+            envelope.held = false;
+            //A note shorter than the attack and hold still gets both
+            if envelope.stage == Stage::Hold && envelope.time >= mode.hold {
+                envelope.release(mode);
+            }
+        }
+    }
+
+    /// Write the current levels as the dimmer of every head, or hand the heads back to the
+    /// params when the mode is off
+    pub fn render(&mut self, mode: &NoteMode, overrides: &HeadOverrides) {
+        if !mode.enabled {
+            if self.active {
+                self.envelopes = [HeadEnvelope::default(); NUM_PARAMS];
+                overrides.clear();
+                self.active = false;
+            }
+            return;
+        }
+        if !mode.hue {
+            for head in 0..NUM_PARAMS {
+                for field in [HeadField::Red, HeadField::Green, HeadField::Blue] {
+                    overrides.values[head][field as usize].store(NO_OVERRIDE, Ordering::Relaxed);
+                }
+            }
+        }
+        for (head, envelope) in self.envelopes.iter().enumerate() {
+            overrides.set(head, HeadField::Dimmer, envelope.level(mode));
+        }
+        self.active = true;
+    }
+
+    pub fn advance(&mut self, mode: &NoteMode, seconds: f32) {
+        for envelope in self.envelopes.iter_mut() {
+            if envelope.stage != Stage::Idle {
+                envelope.advance(mode, seconds);
+            }
+        }
+    }
+}
+
+/// Full saturation and value, `hue` 0-1
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    let channel = |offset: f32| {
+        let k = (offset + hue * 6.0) % 6.0;
+        1.0 - (k.min(4.0 - k).clamp(0.0, 1.0))
+    };
+    [channel(5.0), channel(3.0), channel(1.0)]
+}
@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::address::ParamId;
+
+/// Marks a param nothing plays, or an override that hasn't seen its param yet
+const NO_OVERRIDE: u32 = f32::NAN.to_bits();
+
+/// Values something outside the host plays on params, in param units with enums as their variant
+/// index. Each one remembers the param value it took over from and lets go as soon as the host
+/// moves the param, so automation and GUI edits win again. Indexed like `ParamId::all()`
+pub struct ParamOverrides {
+    values: Vec<AtomicU32>,
+    //Param value when the override was set, NaN until process() has looked at it
+    bases: Vec<AtomicU32>,
+}
+
+impl Default for ParamOverrides {
+    fn default() -> Self {
+        Self {
+            values: ParamId::all()
+                .map(|_| AtomicU32::new(NO_OVERRIDE))
+                .collect(),
+            bases: ParamId::all()
+                .map(|_| AtomicU32::new(NO_OVERRIDE))
+                .collect(),
+        }
+    }
+}
+
+impl ParamOverrides {
+    pub fn get(&self, id: ParamId) -> Option<f32> {
+        let value = f32::from_bits(self.values.get(id.index())?.load(Ordering::Relaxed));
+        (!value.is_nan()).then_some(value)
+    }
+
+    /// Safe to call from any thread, the next `update()` takes the param value it replaces
+    pub fn set(&self, id: ParamId, value: f32) {
+        if let (Some(old), Some(base)) = (self.values.get(id.index()), self.bases.get(id.index())) {
+            old.store(value.to_bits(), Ordering::Relaxed);
+            base.store(NO_OVERRIDE, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns true if there was an override
+    pub fn clear(&self, id: ParamId) -> bool {
+        self.values
+            .get(id.index())
+            .is_some_and(|old| old.swap(NO_OVERRIDE, Ordering::Relaxed) != NO_OVERRIDE)
+    }
+
+    /// Safe to call from process(). `param_value` is what the host has for a param. Takes the
+    /// param values new overrides replace and drops the overrides whose param moved since, calls
+    /// `on_change` for both
+    pub fn update(&self, param_value: impl Fn(ParamId) -> f32, mut on_change: impl FnMut(ParamId)) {
+        for ((value, base), id) in self.values.iter().zip(&self.bases).zip(ParamId::all()) {
+            let bits = value.load(Ordering::Relaxed);
+            if bits == NO_OVERRIDE {
+                continue;
+            }
+            let current = param_value(id);
+            let base_value = f32::from_bits(base.load(Ordering::Relaxed));
+            if base_value.is_nan() {
+                base.store(current.to_bits(), Ordering::Relaxed);
+                on_change(id);
+            } else if base_value != current {
+                //Leave a value another thread just set for the next block
+                let released = value
+                    .compare_exchange(bits, NO_OVERRIDE, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok();
+                if released {
+                    on_change(id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_lets_go_once_the_host_moves_the_param() {
+        let overrides = ParamOverrides::default();
+        let mut changed = Vec::new();
+        overrides.set(ParamId::Zoom, 0.8);
+        overrides.update(|_| 0.2, |id| changed.push(id));
+        assert_eq!(overrides.get(ParamId::Zoom), Some(0.8));
+        assert_eq!(changed, vec![ParamId::Zoom]);
+
+        //Same host value, the override stays
+        overrides.update(|_| 0.2, |id| changed.push(id));
+        assert_eq!(overrides.get(ParamId::Zoom), Some(0.8));
+        assert_eq!(changed.len(), 1);
+
+        overrides.update(|_| 0.3, |id| changed.push(id));
+        assert_eq!(overrides.get(ParamId::Zoom), None);
+        assert_eq!(changed.len(), 2);
+    }
+
+    #[test]
+    fn new_value_takes_the_param_over_again() {
+        let overrides = ParamOverrides::default();
+        overrides.set(ParamId::Zoom, 0.8);
+        overrides.update(|_| 0.2, |_| {});
+        overrides.set(ParamId::Zoom, 0.6);
+        overrides.update(|_| 0.3, |_| {});
+        assert_eq!(overrides.get(ParamId::Zoom), Some(0.6));
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::hint;
+use std::sync::atomic::{fence, AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use crate::address::ParamId;
+use crate::{OsClaPhotonParams, OscParamType};
+
+/// How often the worker sends state frames
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum OutputRate {
+    /// Every audio block that changed something, timed to the sample
+    #[default]
+    OnChange,
+    Hz30,
+    Hz60,
+    Hz120,
+}
+
+impl OutputRate {
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputRate::OnChange => "On Change",
+            OutputRate::Hz30 => "30 Hz",
+            OutputRate::Hz60 => "60 Hz",
+            OutputRate::Hz120 => "120 Hz",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            OutputRate::OnChange => OutputRate::Hz30,
+            OutputRate::Hz30 => OutputRate::Hz60,
+            OutputRate::Hz60 => OutputRate::Hz120,
+            OutputRate::Hz120 => OutputRate::OnChange,
+        }
+    }
+
+    /// None when frames follow the audio blocks
+    pub fn hz(self) -> Option<f64> {
+        match self {
+            OutputRate::OnChange => None,
+            OutputRate::Hz30 => Some(30.0),
+            OutputRate::Hz60 => Some(60.0),
+            OutputRate::Hz120 => Some(120.0),
+        }
+    }
+}
+
+/// Latest value of every param, written by process() and read by the worker at its own rate.
+/// Values are stored as f32 bits so neither side ever locks or allocates.
+/// A generation counter around every publish lets the worker retry instead of taking a frame
+/// that is half old and half new.
+pub struct ParamSnapshot {
+    /// Set by the worker while a fixed output rate is used, process() only publishes then
+    enabled: AtomicBool,
+    //Cleared when enabled, until process() has run the values may be stale
+    published: AtomicBool,
+    //Odd while process() is writing the values
+    generation: AtomicUsize,
+    values: Vec<AtomicU32>,
+}
+
+impl Default for ParamSnapshot {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            published: AtomicBool::new(false),
+            generation: AtomicUsize::new(0),
+            values: ParamId::all().map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+}
+
+impl ParamSnapshot {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.published.store(false, Ordering::Release);
+        self.enabled.store(enabled, Ordering::Release);
+    }
+
+    /// Safe to call from process()
+    pub fn publish(&self, params: &OsClaPhotonParams) {
+        //process() is the only writer so the counter can't move under us
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation
+            .store(generation.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        for id in ParamId::all() {
+            if let Some(value) = self.values.get(id.index()) {
+                value.store(params.osc_value(id).to_bits(), Ordering::Relaxed);
+            }
+        }
+        self.generation
+            .store(generation.wrapping_add(2), Ordering::Release);
+        self.published.store(true, Ordering::Release);
+    }
+
+    /// Params whose published value differs from `last`, the value the worker last took
+    pub fn changes(&self, last: impl Fn(ParamId) -> f32) -> Vec<OscParamType> {
+        if !self.published.load(Ordering::Acquire) {
+            return Vec::new();
+        }
+        let mut frame = Vec::with_capacity(self.values.len());
+        loop {
+            let before = self.generation.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                hint::spin_loop();
+                continue;
+            }
+            frame.clear();
+            frame.extend(
+                self.values
+                    .iter()
+                    .map(|value| f32::from_bits(value.load(Ordering::Relaxed))),
+            );
+            fence(Ordering::Acquire);
+            if self.generation.load(Ordering::Relaxed) == before {
+                break;
+            }
+        }
+        ParamId::all()
+            .filter_map(|id| {
+                let value = *frame.get(id.index())?;
+                (value != last(id)).then_some(OscParamType { id, value })
+            })
+            .collect()
+    }
+}
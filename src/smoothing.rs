@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::address::ParamId;
+use crate::feedback::SentValues;
+use crate::OscParamType;
+
+/// How often interpolated frames go out while a param is still moving, unless a fixed output
+/// rate is set
+pub const SMOOTHING_FRAME_RATE: f64 = 60.0;
+/// Exponential smoothing jumps to the target once it is this close
+const EXP_SNAP_DISTANCE: f32 = 1e-4;
+/// Longest step we take in one frame, so a stalled worker doesnt cause a jump
+const MAX_FRAME_TIME: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum SmoothingStyle {
+    #[default]
+    Off,
+    /// Constant speed, reaches the target after `amount` seconds
+    Linear,
+    /// One pole lowpass with an `amount` seconds time constant
+    Exponential,
+    /// Constant speed of at most `amount` units per second
+    Slew,
+}
+
+impl SmoothingStyle {
+    pub fn name(self) -> &'static str {
+        match self {
+            SmoothingStyle::Off => "Off",
+            SmoothingStyle::Linear => "Linear",
+            SmoothingStyle::Exponential => "Exp",
+            SmoothingStyle::Slew => "Slew",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SmoothingStyle::Off => SmoothingStyle::Linear,
+            SmoothingStyle::Linear => SmoothingStyle::Exponential,
+            SmoothingStyle::Exponential => SmoothingStyle::Slew,
+            SmoothingStyle::Slew => SmoothingStyle::Off,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OscSmoothing {
+    pub style: SmoothingStyle,
+    /// Seconds for `Linear` and `Exponential`, units per second for `Slew`
+    pub amount: f32,
+}
+
+impl Default for OscSmoothing {
+    fn default() -> Self {
+        Self {
+            style: SmoothingStyle::Off,
+            amount: 0.2,
+        }
+    }
+}
+
+/// Smoothing for the params that have it, keyed by host param id like the mappings
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OscSmoothings {
+    pub params: HashMap<String, OscSmoothing>,
+}
+
+impl OscSmoothings {
+    /// Enums are never smoothed, a value between two variants means nothing
+    pub fn get(&self, id: ParamId) -> Option<&OscSmoothing> {
+        if id.is_enum() {
+            return None;
+        }
+        self.params.get(&id.host_id()).filter(|smoothing| {
+            smoothing.style != SmoothingStyle::Off && smoothing.amount > 0.0
+        })
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct SmootherState {
+    current: f32,
+    target: f32,
+    /// Units per second, only used by linear ramps
+    speed: f32,
+}
+
+/// Interpolation state on the worker, one entry per param indexed by `ParamId::index()`
+pub struct Smoothers {
+    pub settings: OscSmoothings,
+    states: Vec<SmootherState>,
+    frame_rate: f64,
+    last_frame: f64,
+    /// Only set while something is still moving
+    next_frame: Option<f64>,
+    /// Told when a ramp starts and ends so feedback knows which frames are still in flight
+    sent: Arc<SentValues>,
+}
+
+impl Smoothers {
+    pub fn new(settings: OscSmoothings, sent: Arc<SentValues>) -> Self {
+        Self {
+            settings,
+            states: vec![SmootherState::default(); ParamId::all().count()],
+            frame_rate: SMOOTHING_FRAME_RATE,
+            last_frame: 0.0,
+            next_frame: None,
+            sent,
+        }
+    }
+
+    pub fn set_frame_rate(&mut self, frame_rate: f64) {
+        self.frame_rate = frame_rate;
+    }
+
+    /// The value a param is heading to, or sitting at
+    pub fn target(&self, id: ParamId) -> f32 {
+        self.states.get(id.index()).map_or(0.0, |state| state.target)
+    }
+
+    /// Jump straight to these values, e.g. when the full state is sent
+    pub fn snap(&mut self, params: &[OscParamType]) {
+        for param in params {
+            if let Some(state) = self.states.get_mut(param.id.index()) {
+                state.current = param.value;
+                state.target = param.value;
+                self.sent.end_ramp(param.id);
+            }
+        }
+    }
+
+    /// Smoothed params get a new target, the rest is returned to be sent as is
+    pub fn retarget(&mut self, params: Vec<OscParamType>, now: f64) -> Vec<OscParamType> {
+        let mut immediate = Vec::with_capacity(params.len());
+        for param in params {
+            let state = match self.states.get_mut(param.id.index()) {
+                Some(state) => state,
+                None => continue,
+            };
+            match self.settings.get(param.id) {
+                Some(smoothing) => {
+                    state.target = param.value;
+                    self.sent.start_ramp(param.id);
+                    state.speed = (state.target - state.current).abs() / smoothing.amount.max(f32::EPSILON);
+                    if self.next_frame.is_none() {
+                        self.last_frame = now;
+                        self.next_frame = Some(now + 1.0 / self.frame_rate);
+                    }
+                }
+                None => {
+                    state.current = param.value;
+                    state.target = param.value;
+                    self.sent.end_ramp(param.id);
+                    immediate.push(param);
+                }
+            }
+        }
+        immediate
+    }
+
+    pub fn next_frame(&self) -> Option<f64> {
+        self.next_frame
+    }
+
+    /// Move every param that hasnt reached its target yet and return the new values
+    pub fn tick(&mut self, now: f64) -> Vec<OscParamType> {
+        let dt = ((now - self.last_frame) as f32).clamp(0.0, MAX_FRAME_TIME);
+        self.last_frame = now;
+        let mut frame = Vec::new();
+        for id in ParamId::all() {
+            let state = match self.states.get_mut(id.index()) {
+                Some(state) if state.current != state.target => state,
+                _ => continue,
+            };
+            state.current = match self.settings.get(id) {
+                Some(smoothing) => match smoothing.style {
+                    SmoothingStyle::Linear => approach(state.current, state.target, state.speed * dt),
+                    SmoothingStyle::Exponential => {
+                        let coefficient = 1.0 - (-dt / smoothing.amount.max(f32::EPSILON)).exp();
+                        let next = state.current + (state.target - state.current) * coefficient;
+                        if (state.target - next).abs() < EXP_SNAP_DISTANCE {
+                            state.target
+                        } else {
+                            next
+                        }
+                    }
+                    SmoothingStyle::Slew => approach(state.current, state.target, smoothing.amount * dt),
+                    SmoothingStyle::Off => state.target,
+                },
+                //Smoothing was turned off halfway
+                None => state.target,
+            };
+            //The last frame is only compared as the last value sent
+            if state.current == state.target {
+                self.sent.end_ramp(id);
+            }
+            frame.push(OscParamType {
+                id,
+                value: state.current,
+            });
+        }
+        let moving = self.states.iter().any(|state| state.current != state.target);
+        self.next_frame = moving.then_some(now + 1.0 / self.frame_rate);
+        frame
+    }
+}
+
+fn approach(current: f32, target: f32, max_step: f32) -> f32 {
+    let difference = target - current;
+    if difference.abs() <= max_step {
+        target
+    } else {
+        current + max_step.copysign(difference)
+    }
+}
@@ -0,0 +1,210 @@
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use crate::timing::unix_now_secs;
+
+/// Whether TD-Photon answers our heartbeats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PeerState {
+    /// Heartbeat is off or nothing has been heard yet
+    Unknown,
+    /// Every destination acked within the timeout
+    Online,
+    /// At least one destination didn't
+    Offline,
+    /// No listen port, acks can't reach us
+    NoListener,
+}
+
+impl PeerState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => PeerState::Online,
+            2 => PeerState::Offline,
+            3 => PeerState::NoListener,
+            _ => PeerState::Unknown,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            PeerState::Unknown => 0,
+            PeerState::Online => 1,
+            PeerState::Offline => 2,
+            PeerState::NoListener => 3,
+        }
+    }
+}
+
+/// How one destination answers our heartbeats
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerStatus {
+    pub name: String,
+    pub online: bool,
+    /// UNIX seconds
+    pub last_ack: Option<f64>,
+}
+
+/// Delivery counters shared between process(), the OSC worker and the editor. The counters are
+/// atomics so process() can count drops without locking.
+#[derive(Default)]
+pub struct OscStats {
+    pub packets_sent: AtomicU64,
+    pub bytes_sent: AtomicU64,
+    pub send_errors: AtomicU64,
+    /// Channel messages process() had to drop because the worker fell behind
+    pub dropped: AtomicU64,
+    /// Messages waiting in the channel the last time the worker looked
+    pub queued: AtomicUsize,
+    pub last_error: RwLock<String>,
+    /// Feedback values that made it to a param, through the host or as an override
+    pub feedback_applied: AtomicU64,
+    /// Feedback we couldn't use: unknown addresses or arguments, or params something else plays
+    pub feedback_dropped: AtomicU64,
+    /// UNIX seconds of the last heartbeat ack per address base the worker sends to, 0 if none yet.
+    /// Destinations that share a base share their acks
+    acks: RwLock<Vec<(String, f64)>>,
+    /// Whether the feedback server has a socket acks can arrive on
+    listening: AtomicBool,
+    peer_state: AtomicU8,
+    peers: RwLock<Vec<PeerStatus>>,
+}
+
+impl OscStats {
+    pub fn record_sent(&self, bytes: usize) {
+        self.packets_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self, error: String) {
+        self.send_errors.fetch_add(1, Ordering::Relaxed);
+        *self.last_error.write() = error;
+    }
+
+    /// Safe to call from process()
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_feedback(&self, applied: bool) {
+        let counter = if applied { &self.feedback_applied } else { &self.feedback_dropped };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Acks for an address base nothing is sent to are ignored
+    pub fn record_ack(&self, address_base: &str) {
+        let mut acks = self.acks.write();
+        if let Some((_, last_ack)) = acks.iter_mut().find(|(base, _)| base == address_base) {
+            *last_ack = unix_now_secs();
+        }
+    }
+
+    /// Only keep and accept acks for these address bases
+    pub fn watch_acks<'a>(&self, address_bases: impl Iterator<Item = &'a str>) {
+        let mut acks = self.acks.write();
+        let watched: Vec<(String, f64)> = address_bases
+            .map(|address_base| {
+                let last_ack = acks
+                    .iter()
+                    .find(|(base, _)| base == address_base)
+                    .map_or(0.0, |(_, last_ack)| *last_ack);
+                (address_base.to_string(), last_ack)
+            })
+            .collect();
+        *acks = watched;
+    }
+
+    pub fn last_ack(&self, address_base: &str) -> Option<f64> {
+        self.acks
+            .read()
+            .iter()
+            .find(|(base, last_ack)| base == address_base && *last_ack > 0.0)
+            .map(|(_, last_ack)| *last_ack)
+    }
+
+    pub fn set_listening(&self, listening: bool) {
+        self.listening.store(listening, Ordering::Relaxed);
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.listening.load(Ordering::Relaxed)
+    }
+
+    pub fn peer_state(&self) -> PeerState {
+        PeerState::from_u8(self.peer_state.load(Ordering::Relaxed))
+    }
+
+    /// Returns the previous state
+    pub fn set_peer_state(&self, peer_state: PeerState) -> PeerState {
+        PeerState::from_u8(self.peer_state.swap(peer_state.to_u8(), Ordering::Relaxed))
+    }
+
+    pub fn set_peers(&self, peers: Vec<PeerStatus>) {
+        *self.peers.write() = peers;
+    }
+
+    pub fn sent_summary(&self) -> String {
+        format!(
+            "Sent: {} packets, {:.1} kB",
+            self.packets_sent.load(Ordering::Relaxed),
+            self.bytes_sent.load(Ordering::Relaxed) as f64 / 1024.0
+        )
+    }
+
+    pub fn error_summary(&self, channel_capacity: usize) -> String {
+        format!(
+            "Errors: {}  Dropped: {}  Queue: {}/{}",
+            self.send_errors.load(Ordering::Relaxed),
+            self.dropped.load(Ordering::Relaxed),
+            self.queued.load(Ordering::Relaxed),
+            channel_capacity
+        )
+    }
+
+    pub fn last_error_summary(&self) -> String {
+        let last_error = self.last_error.read();
+        if last_error.is_empty() {
+            "Last error: none".to_string()
+        } else {
+            format!("Last error: {}", last_error)
+        }
+    }
+
+    pub fn feedback_summary(&self) -> String {
+        format!(
+            "Feedback: {} applied, {} dropped",
+            self.feedback_applied.load(Ordering::Relaxed),
+            self.feedback_dropped.load(Ordering::Relaxed)
+        )
+    }
+
+    pub fn peer_summary(&self) -> String {
+        let peers = self.peers.read();
+        match (self.peer_state(), peers.as_slice()) {
+            (PeerState::Unknown, _) => "Peer: unknown".to_string(),
+            (PeerState::NoListener, _) => "Peer: no feedback port".to_string(),
+            (_, []) => "Peer: no destination".to_string(),
+            (_, [peer]) => match peer.last_ack {
+                Some(last_ack) => format!(
+                    "Peer: {} (last ack {:.1} s ago)",
+                    if peer.online { "online" } else { "offline" },
+                    unix_now_secs() - last_ack
+                ),
+                None => "Peer: offline (no ack yet)".to_string(),
+            },
+            (_, peers) => {
+                let offline: Vec<&str> = peers
+                    .iter()
+                    .filter(|peer| !peer.online)
+                    .map(|peer| peer.name.as_str())
+                    .collect();
+                let online = peers.len() - offline.len();
+                if offline.is_empty() {
+                    format!("Peers: {}/{} online", online, peers.len())
+                } else {
+                    format!("Peers: {}/{} online, offline: {}", online, peers.len(), offline.join(", "))
+                }
+            }
+        }
+    }
+}
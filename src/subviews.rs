@@ -1,85 +1,154 @@
 use std::sync::Arc;
+use std::time::Instant;
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::widgets::*;
 
-use crate::{editor::OsClaPhotonEditorEvent, editor::OscSettings, OsClaPhotonParams};
+use crate::address::{is_valid_address_base, HeadField, ParamId};
+use crate::heartbeat::is_valid_seconds;
+use crate::mapping::MappingCurve;
+use crate::stats::OscStats;
+use crate::transport::{parse_host, HostInput};
+use crate::{editor::OsClaPhotonEditorEvent, editor::OscSettings, OsClaPhotonParams, NUM_PARAMS, OSC_CHANNEL_CAPACITY};
+use crate::{enum_variant_names, is_destructive_variant};
+
+/// Quick colours for the selected heads
+const PALETTE: [[f32; 3]; 8] = [
+    [1.0, 1.0, 1.0],
+    [1.0, 0.0, 0.0],
+    [1.0, 0.5, 0.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 1.0, 1.0],
+    [0.0, 0.0, 1.0],
+    [1.0, 0.0, 1.0],
+];
+
+fn swatch_color([red, green, blue]: [f32; 3]) -> Color {
+    Color::rgb(
+        (red * 255.0).round() as u8,
+        (green * 255.0).round() as u8,
+        (blue * 255.0).round() as u8,
+    )
+}
+
+fn hex_color([red, green, blue]: [f32; 3]) -> String {
+    let byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(red), byte(green), byte(blue))
+}
+
+/// `#rrggbb` or `rrggbb`, channels 0-1
+fn parse_hex_color(text: &str) -> Option<[f32; 3]> {
+    let hex = text.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |range: std::ops::Range<usize>| u8::from_str_radix(&hex[range], 16).ok().map(|byte| byte as f32 / 255.0);
+    Some([channel(0..2)?, channel(2..4)?, channel(4..6)?])
+}
 
 pub struct ParamView;
 
 impl ParamView {
-    pub fn new<P>(cx: &mut Context, params: P) -> Handle<Self>
+    pub fn new<P, A>(cx: &mut Context, params: P, armed_variant: A) -> Handle<Self>
     where
         P: Lens<Target = Arc<OsClaPhotonParams>> + Copy,
+        A: Lens<Target = Option<(ParamId, usize, Instant)>> + Copy,
     {
-        //TODO handle param names
         Self.build(cx, |cx| {
-            //ToDo: Control enum params:
-            //https://github.com/vizia/vizia/blob/main/examples/views/dropdown.rs
-            //https://monadical.com/posts/shades-of-rust-gui-library-list.html#11-Vizia
-            //https://github.com/robbert-vdh/nih-plug/blob/master/nih_plug_vizia/src/widgets/param_button.rs
-            //https://github.com/robbert-vdh/nih-plug/blob/master/src/params/enums.rs
+            for id in [ParamId::Control, ParamId::Shutter, ParamId::Rotation] {
+                EnumSelector::new(cx, params, armed_variant, id);
+            }
             HStack::new(cx, |cx| {
-                Label::new(cx, "Beams Nr").class("label");
+                Label::new(cx, ParamId::BeamNumber.global_name()).class("label");
                 ParamSlider::new(cx, params, |params| &params.beam_number)
                     .class("widget");
             })
             .class("row");
             HStack::new(cx, |cx| {
-                Label::new(cx, "Rotation Speed").class("label");
+                Label::new(cx, ParamId::RotSpeed.global_name()).class("label");
                 ParamSlider::new(cx, params, |params| &params.rot_speed)
                     .class("widget");
             })
             .class("row");
             HStack::new(cx, |cx| {
-                Label::new(cx, "Beams Size").class("label");
+                Label::new(cx, ParamId::BeamSize.global_name()).class("label");
                 ParamSlider::new(cx, params, |params| &params.beam_size)
                     .class("widget");
             })
             .class("row");
             HStack::new(cx, |cx| {
-                Label::new(cx, "Zoom").class("label");
+                Label::new(cx, ParamId::Zoom.global_name()).class("label");
                 ParamSlider::new(cx, params, |params| &params.zoom)
                     .class("widget");
             })
             .class("row");
             HStack::new(cx, |cx| {
-                Label::new(cx, "Zoom Speed").class("label");
+                Label::new(cx, ParamId::ZoomSpeed.global_name()).class("label");
                 ParamSlider::new(cx, params, |params| &params.zoom_speed)
                     .class("widget");
             })
             .class("row");
             HStack::new(cx, |cx| {
-                Label::new(cx, "Offset").class("label");
+                Label::new(cx, ParamId::Offset.global_name()).class("label");
                 ParamSlider::new(cx, params, |params| &params.offset)
                     .class("widget");
             })
             .class("row");
+        })
+    }
+}
 
+impl View for ParamView {
+    fn element(&self) -> Option<&'static str> {
+        Some("generic-ui")
+    }
+}
 
 
-            // HStack::new(cx, |cx| {
-            //     Label::new(cx, "param7").class("label");
-            //     ParamSlider::new(cx, params, |params| &params.param7)
-            //         .class("widget");
-            // })
-            // .class("row");
-            // HStack::new(cx, |cx| {
-            //     Label::new(cx, "param8").class("label");
-            //     ParamSlider::new(cx, params, |params| &params.param8)
-            //         .class("widget");
-            // })
-            // .class("row");
+/// Segmented buttons for an enum param, one per variant. Goes through the host like the sliders
+/// so the edit can be recorded, resets need a second click
+pub struct EnumSelector;
+
+impl EnumSelector {
+    pub fn new<P, A>(cx: &mut Context, params: P, armed_variant: A, id: ParamId) -> Handle<Self>
+    where
+        P: Lens<Target = Arc<OsClaPhotonParams>> + Copy,
+        A: Lens<Target = Option<(ParamId, usize, Instant)>> + Copy,
+    {
+        Self.build(cx, |cx| {
+            Label::new(cx, id.name()).class("label");
+            HStack::new(cx, |cx| {
+                for (index, variant) in enum_variant_names(id).iter().enumerate() {
+                    Button::new(
+                        cx,
+                        move |cx| cx.emit(OsClaPhotonEditorEvent::SelectEnumVariant(id, index)),
+                        move |cx| {
+                            Label::new(cx, armed_variant.map(move |armed_variant| {
+                                match armed_variant {
+                                    Some((armed_id, armed_index, _))
+                                        if *armed_id == id && *armed_index == index => "Confirm".to_string(),
+                                    _ => variant.to_string(),
+                                }
+                            }))
+                        },
+                    )
+                    .toggle_class("selected", params.map(move |params| params.osc_value(id) as usize == index))
+                    .toggle_class("destructive", is_destructive_variant(id, index))
+                    .width(Stretch(1.0));
+                }
+            })
+            .col_between(Pixels(2.0))
+            .class("row");
         })
     }
 }
 
-impl View for ParamView {
+impl View for EnumSelector {
     fn element(&self) -> Option<&'static str> {
         Some("generic-ui")
     }
 }
 
-
 pub struct SettingsView;
 
 impl SettingsView {
@@ -94,12 +163,12 @@ impl SettingsView {
                 Label::new(cx, "TD OSC IP").class("label");
                 Textbox::new(cx, settings.map(|settings| settings.osc_server_address.clone()))
                     .on_edit(move |cx, text| {
-                        //TODO: validate
                         cx.emit(OsClaPhotonEditorEvent::SetOscServerAddress(text));
                     })
                     .on_submit(|cx,  _, _| {
                         cx.emit(OsClaPhotonEditorEvent::ConnectionChange);
                     })
+                    .toggle_class("invalid", settings.map(|settings| settings.server_address_error.is_some()))
                     .width(Pixels(135.0)); // 200 = 135 + 60 + 5
                 Textbox::new(cx, settings.map(|settings| settings.osc_server_port))
                     .on_edit(move |cx, text| {
@@ -117,31 +186,570 @@ impl SettingsView {
             })
             .class("row");
             // .col_between(Pixels(5.0));
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Full State").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::SendAll),
+                    |cx| Label::new(cx, "Send All"),
+                )
+                .width(Pixels(95.0));
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Transport").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleTransport),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.osc_transport.name().to_string())),
+                )
+                .width(Pixels(95.0));
+                //Framing only matters for TCP but stays visible so the layout doesnt jump
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleTcpFraming),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.osc_tcp_framing.name().to_string())),
+                )
+                .width(Pixels(100.0));
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Multicast Group").class("label");
+                Textbox::new(cx, settings.map(|settings| settings.osc_multicast.group.clone()))
+                    .on_edit(move |cx, text| {
+                        cx.emit(OsClaPhotonEditorEvent::SetMulticastGroup(text));
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MulticastChange);
+                    })
+                    .width(Pixels(135.0));
+                Textbox::new(cx, settings.map(|settings| settings.osc_multicast.ttl))
+                    .on_edit(move |cx, text| {
+                        if let Ok(val) = text.parse::<u8>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetMulticastTtl(val as u32));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MulticastChange);
+                    })
+                    .width(Pixels(60.0));
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Multicast Interface").class("label");
+                //IP or interface name, empty lets the OS pick
+                Textbox::new(cx, settings.map(|settings| settings.osc_multicast.interface.clone()))
+                    .on_edit(move |cx, text| {
+                        cx.emit(OsClaPhotonEditorEvent::SetMulticastInterface(text));
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MulticastChange);
+                    })
+                    .width(Pixels(135.0));
+                Checkbox::new(cx, settings.map(|settings| settings.osc_multicast.loopback))
+                    .on_toggle(|cx| {
+                        cx.emit(OsClaPhotonEditorEvent::ToggleMulticastLoopback);
+                    });
+                Label::new(cx, "Loop").class("label");
+            })
+            .class("row");
             HStack::new(cx, |cx| {
                 Label::new(cx, "OSC Address Base").class("label");
                 Textbox::new(cx, settings.map(|settings| settings.osc_address_base.clone()))
                     .on_edit(move |cx, text| {
-                        //TODO: validate
                         cx.emit(OsClaPhotonEditorEvent::SetOscAddressBase(text));
                     })
                     .on_submit(|cx,  _, _| {
                         cx.emit(OsClaPhotonEditorEvent::AddressBaseChange);
                     })
+                    .toggle_class("invalid", settings.map(|settings| !is_valid_address_base(&settings.osc_address_base)))
                     .width(Pixels(200.0));
             })
             .class("row");
-            // HStack::new(cx, |cx| {
-            //     Label::new(cx, "Send MIDI").class("label");
-            //     ParamSlider::new(cx, params, |params| &params.flag_send_midi)
-            //         .class("widget");
-            // })
-            // .class("row");
-            // HStack::new(cx, |cx| {
-            //     Label::new(cx, "Send Audio").class("label");
-            //     ParamSlider::new(cx, params, |params| &params.flag_send_audio)
-            //     .class("widget");
-            // })
-            // .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Address Scheme").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleAddressScheme),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.osc_address_schema.scheme.name().to_string())),
+                )
+                .width(Pixels(200.0));
+            })
+            .class("row");
+            //Per param template, placeholders {base} {group} {head} {id} {name}
+            HStack::new(cx, |cx| {
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::StepSelectedParam(-1)),
+                    |cx| Label::new(cx, "<"),
+                )
+                .width(Pixels(25.0));
+                Label::new(cx, settings.map(|settings| settings.selected_id().name()))
+                    .class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::StepSelectedParam(1)),
+                    |cx| Label::new(cx, ">"),
+                )
+                .width(Pixels(25.0));
+                Textbox::new(cx, settings.map(|settings| {
+                    settings
+                        .osc_address_schema
+                        .template(settings.selected_id())
+                        .to_string()
+                }))
+                    .on_edit(move |cx, text| {
+                        cx.emit(OsClaPhotonEditorEvent::SetParamTemplate(text));
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::AddressSchemaChange);
+                    })
+                    .toggle_class("invalid", settings.map(|settings| !settings.template_valid()))
+                    .width(Pixels(200.0));
+            })
+            .class("row");
+            //Output mapping of the same param, enums are never mapped
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Map Range").class("label");
+                Textbox::new(cx, settings.map(|settings| settings.selected_mapping().min))
+                    .on_edit(move |cx, text| {
+                        if let Ok(val) = text.parse::<f32>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetMappingMin(val));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MappingsChange);
+                    })
+                    .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| settings.selected_mapping().max))
+                    .on_edit(move |cx, text| {
+                        if let Ok(val) = text.parse::<f32>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetMappingMax(val));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MappingsChange);
+                    })
+                    .width(Pixels(60.0));
+                Checkbox::new(cx, settings.map(|settings| settings.selected_mapping().invert))
+                    .on_toggle(|cx| {
+                        cx.emit(OsClaPhotonEditorEvent::ToggleMappingInvert);
+                    });
+                Label::new(cx, "Invert").class("label");
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Map Curve").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleMappingCurve),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.selected_mapping().curve.name().to_string())),
+                )
+                .width(Pixels(70.0));
+                //Quantize steps, 0 is off
+                Textbox::new(cx, settings.map(|settings| settings.selected_mapping().steps))
+                    .on_edit(move |cx, text| {
+                        if let Ok(val) = text.parse::<u32>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetMappingSteps(val));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MappingsChange);
+                    })
+                    .width(Pixels(60.0));
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleMappingOutput),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.selected_mapping().output.name().to_string())),
+                )
+                .width(Pixels(60.0));
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Smoothing").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleSmoothingStyle),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.selected_smoothing().style.name().to_string())),
+                )
+                .width(Pixels(70.0));
+                //Seconds for Linear and Exp, units per second for Slew
+                Textbox::new(cx, settings.map(|settings| settings.selected_smoothing().amount))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val > 0.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetSmoothingAmount(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::SmoothingsChange);
+                    })
+                    .width(Pixels(60.0));
+            })
+            .class("row");
+            //MIDI binding of the same param
+            HStack::new(cx, |cx| {
+                Label::new(cx, "MIDI").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::ToggleMidiLearn),
+                    move |cx| Label::new(cx, settings.map(|settings| {
+                        if settings.midi_learning { "Cancel" } else { "Learn" }.to_string()
+                    })),
+                )
+                .width(Pixels(70.0));
+                Label::new(cx, settings.map(|settings| {
+                    settings
+                        .selected_midi_binding()
+                        .map_or("Not bound".to_string(), |binding| binding.control.name())
+                }))
+                    .class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::ClearMidiBinding),
+                    |cx| Label::new(cx, "X"),
+                )
+                .width(Pixels(25.0));
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "MIDI Range").class("label");
+                Textbox::new(cx, settings.map(|settings| {
+                    settings.selected_midi_binding().map_or(0.0, |binding| binding.min)
+                }))
+                    .on_edit(move |cx, text| {
+                        if let Ok(val) = text.parse::<f32>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetMidiMin(val));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MidiBindingsChange);
+                    })
+                    .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| {
+                    settings.selected_midi_binding().map_or(1.0, |binding| binding.max)
+                }))
+                    .on_edit(move |cx, text| {
+                        if let Ok(val) = text.parse::<f32>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetMidiMax(val));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MidiBindingsChange);
+                    })
+                    .width(Pixels(60.0));
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleMidiCurve),
+                    move |cx| Label::new(cx, settings.map(|settings| {
+                        settings
+                            .selected_midi_binding()
+                            .map_or(MappingCurve::Linear, |binding| binding.curve)
+                            .name()
+                            .to_string()
+                    })),
+                )
+                .width(Pixels(70.0));
+            })
+            .class("row");
+            //Mirror of the same param on the MIDI output, channel shown 1 based
+            HStack::new(cx, |cx| {
+                Label::new(cx, "MIDI Out").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleMidiOutKind),
+                    move |cx| Label::new(cx, settings.map(|settings| {
+                        settings
+                            .selected_midi_output()
+                            .map_or("Off", |control| control.kind.name())
+                            .to_string()
+                    })),
+                )
+                .width(Pixels(90.0));
+                Textbox::new(cx, settings.map(|settings| {
+                    settings.selected_midi_output().map_or(1, |control| control.channel + 1)
+                }))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<u8>() {
+                            Ok(val) if (1..=16).contains(&val) => {
+                                cx.emit(OsClaPhotonEditorEvent::SetMidiOutChannel(val - 1));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MidiOutputsChange);
+                    })
+                    .width(Pixels(50.0));
+                //CC or NRPN number, too large numbers are clamped to the kind's range
+                Textbox::new(cx, settings.map(|settings| {
+                    settings.selected_midi_output().map_or(0, |control| control.number)
+                }))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<u16>() {
+                            Ok(val) if val < 16384 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetMidiOutNumber(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::MidiOutputsChange);
+                    })
+                    .width(Pixels(60.0));
+            })
+            .class("row");
+            //How much the envelope follower adds to the same param, enums can't follow
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Follow Depth").class("label");
+                Textbox::new(cx, settings.map(|settings| settings.selected_follower_route().depth))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if (0.0..=1.0).contains(&val) => {
+                                cx.emit(OsClaPhotonEditorEvent::SetFollowerDepth(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::FollowerRoutesChange);
+                    })
+                    .width(Pixels(60.0));
+                Checkbox::new(cx, settings.map(|settings| settings.selected_follower_route().invert))
+                    .on_toggle(|cx| {
+                        cx.emit(OsClaPhotonEditorEvent::ToggleFollowerInvert);
+                    });
+                Label::new(cx, "Invert").class("label");
+            })
+            .class("row");
+            //Envelope follower on the input audio, attack and release in seconds
+            HStack::new(cx, |cx| {
+                Checkbox::new(cx, settings.map(|settings| settings.audio_follower.enabled))
+                    .on_toggle(|cx| {
+                        cx.emit(OsClaPhotonEditorEvent::ToggleFollower);
+                    });
+                Label::new(cx, "Follower").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleFollowerMode),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.audio_follower.mode.name().to_string())),
+                )
+                .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| settings.audio_follower.attack))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val >= 0.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetFollowerAttack(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::FollowerChange);
+                    })
+                    .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| settings.audio_follower.release))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val >= 0.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetFollowerRelease(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::FollowerChange);
+                    })
+                    .width(Pixels(60.0));
+            })
+            .class("row");
+            //Gain and threshold in dB, then the current output
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Follower Level").class("label");
+                Textbox::new(cx, settings.map(|settings| settings.audio_follower.gain))
+                    .on_edit(move |cx, text| {
+                        if let Ok(val) = text.parse::<f32>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetFollowerGain(val));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::FollowerChange);
+                    })
+                    .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| settings.audio_follower.threshold))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val < 0.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetFollowerThreshold(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::FollowerChange);
+                    })
+                    .width(Pixels(60.0));
+                Label::new(cx, params.map(|params| format!("{:.2}", params.modulation.level())))
+                    .class("label");
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Output Rate").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleOutputRate),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.osc_output_rate.name().to_string())),
+                )
+                .width(Pixels(200.0));
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Head Output").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleHeadOutput),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.osc_head_output.name().to_string())),
+                )
+                .width(Pixels(200.0));
+            })
+            .class("row");
+            //Control, Shutter and Rotation
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Enum Args").class("label");
+                for id in [ParamId::Control, ParamId::Shutter, ParamId::Rotation] {
+                    Button::new(
+                        cx,
+                        move |cx| cx.emit(OsClaPhotonEditorEvent::CycleEnumArg(id)),
+                        move |cx| Label::new(cx, settings.map(move |settings| settings.osc_enum_args.get(id).name().to_string())),
+                    )
+                    .width(Pixels(65.0));
+                }
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Feedback Port").class("label");
+                Textbox::new(cx, settings.map(|settings| {
+                    settings
+                        .osc_listen_port
+                        .map(|port| port.to_string())
+                        .unwrap_or_default()
+                }))
+                    .on_edit(move |cx, text| {
+                        //Empty turns feedback off
+                        if text.is_empty() {
+                            cx.emit(OsClaPhotonEditorEvent::SetOscListenPort(None));
+                            cx.toggle_class("invalid", false);
+                        } else if let Ok(val) = text.parse::<u16>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetOscListenPort(Some(val)));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::ListenPortChange);
+                    })
+                    .width(Pixels(200.0));
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Checkbox::new(cx, settings.map(|settings| settings.osc_heartbeat.enabled))
+                    .on_toggle(|cx| {
+                        cx.emit(OsClaPhotonEditorEvent::ToggleHeartbeat);
+                    });
+                Label::new(cx, "Heartbeat").class("label");
+                //Interval, timeout and idle resync in seconds, 0.1 to 3600
+                Textbox::new(cx, settings.map(|settings| settings.osc_heartbeat.interval))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if is_valid_seconds(val) => {
+                                cx.emit(OsClaPhotonEditorEvent::SetHeartbeatInterval(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::HeartbeatChange);
+                    })
+                    .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| settings.osc_heartbeat.timeout))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if is_valid_seconds(val) => {
+                                cx.emit(OsClaPhotonEditorEvent::SetHeartbeatTimeout(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::HeartbeatChange);
+                    })
+                    .width(Pixels(60.0));
+                //0 turns the idle resync off
+                Textbox::new(cx, settings.map(|settings| settings.osc_heartbeat.resync_after))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val == 0.0 || is_valid_seconds(val) => {
+                                cx.emit(OsClaPhotonEditorEvent::SetHeartbeatResync(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::HeartbeatChange);
+                    })
+                    .width(Pixels(60.0));
+            })
+            .class("row");
+            //Extra destinations, one row each
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Extra Destinations").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::AddDestination),
+                    |cx| Label::new(cx, "Add"),
+                )
+                .width(Pixels(60.0));
+            })
+            .class("row");
+            Binding::new(cx, settings.map(|settings| settings.osc_destinations.len()), move |cx, len| {
+                for index in 0..len.get(cx) {
+                    DestinationRow::new(cx, settings, index);
+                }
+            });
             VirtualList::new(cx, log, 20.0, |cx, _index, item| {
                 return Label::new(cx, item).left(Pixels(0.0)).class("label");
             })
@@ -156,3 +764,456 @@ impl View for SettingsView {
         Some("generic-ui")
     }
 }
+
+/// One extra OSC destination: enable, ip, port, address base override and remove
+pub struct DestinationRow;
+
+impl DestinationRow {
+    pub fn new<S>(cx: &mut Context, settings: S, index: usize) -> Handle<Self>
+    where
+        S: Lens<Target = OscSettings> + Copy,
+    {
+        Self.build(cx, |cx| {
+            HStack::new(cx, |cx| {
+                Checkbox::new(cx, settings.map(move |settings| {
+                    settings
+                        .osc_destinations
+                        .get(index)
+                        .map_or(false, |destination| destination.enabled)
+                }))
+                    .on_toggle(move |cx| {
+                        cx.emit(OsClaPhotonEditorEvent::ToggleDestination(index));
+                    });
+                Textbox::new(cx, settings.map(move |settings| {
+                    settings
+                        .osc_destinations
+                        .get(index)
+                        .map(|destination| destination.address.clone())
+                        .unwrap_or_default()
+                }))
+                    .on_edit(move |cx, text| {
+                        cx.emit(OsClaPhotonEditorEvent::SetDestinationAddress(index, text));
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::DestinationsChange);
+                    })
+                    .toggle_class("invalid", settings.map(move |settings| {
+                        settings.osc_destinations.get(index).map_or(false, |destination| {
+                            matches!(parse_host(&destination.address), HostInput::Invalid(_))
+                        })
+                    }))
+                    .width(Pixels(110.0));
+                Textbox::new(cx, settings.map(move |settings| {
+                    settings
+                        .osc_destinations
+                        .get(index)
+                        .map_or(0, |destination| destination.port)
+                }))
+                    .on_edit(move |cx, text| {
+                        if let Ok(val) = text.parse::<u16>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetDestinationPort(index, val));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::DestinationsChange);
+                    })
+                    .width(Pixels(60.0));
+                //Empty uses the main address base
+                Textbox::new(cx, settings.map(move |settings| {
+                    settings
+                        .osc_destinations
+                        .get(index)
+                        .and_then(|destination| destination.address_base.clone())
+                        .unwrap_or_default()
+                }))
+                    .on_edit(move |cx, text| {
+                        cx.emit(OsClaPhotonEditorEvent::SetDestinationAddressBase(index, text));
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::DestinationsChange);
+                    })
+                    .toggle_class("invalid", settings.map(move |settings| {
+                        settings.osc_destinations.get(index).map_or(false, |destination| {
+                            !is_valid_address_base(destination.address_base.as_deref().unwrap_or_default())
+                        })
+                    }))
+                    .width(Pixels(90.0));
+                Button::new(
+                    cx,
+                    move |cx| cx.emit(OsClaPhotonEditorEvent::RemoveDestination(index)),
+                    |cx| Label::new(cx, "X"),
+                )
+                .width(Pixels(25.0));
+            })
+            .class("row");
+        })
+    }
+}
+
+impl View for DestinationRow {
+    fn element(&self) -> Option<&'static str> {
+        Some("generic-ui")
+    }
+}
+
+/// Live delivery counters from the OSC worker
+pub struct StatusView;
+
+impl StatusView {
+    pub fn new<S>(cx: &mut Context, stats: S) -> Handle<Self>
+    where
+        S: Lens<Target = Arc<OscStats>> + Copy,
+    {
+        Self.build(cx, |cx| {
+            Label::new(cx, stats.map(|stats| stats.sent_summary()))
+                .left(Pixels(0.0))
+                .class("label");
+            Label::new(cx, stats.map(|stats| stats.error_summary(OSC_CHANNEL_CAPACITY)))
+                .left(Pixels(0.0))
+                .class("label");
+            Label::new(cx, stats.map(|stats| stats.last_error_summary()))
+                .left(Pixels(0.0))
+                .class("label");
+            Label::new(cx, stats.map(|stats| stats.peer_summary()))
+                .left(Pixels(0.0))
+                .class("label");
+            Label::new(cx, stats.map(|stats| stats.feedback_summary()))
+                .left(Pixels(0.0))
+                .class("label");
+        })
+    }
+}
+
+impl View for StatusView {
+    fn element(&self) -> Option<&'static str> {
+        Some("generic-ui")
+    }
+}
+
+/// Every tilt head side by side plus group controls that edit all selected heads at once
+pub struct HeadView;
+
+impl HeadView {
+    pub fn new<P, T, S, G>(cx: &mut Context, params: P, settings: T, selected_heads: S, group_values: G) -> Handle<Self>
+    where
+        P: Lens<Target = Arc<OsClaPhotonParams>> + Copy,
+        T: Lens<Target = OscSettings> + Copy,
+        S: Lens<Target = Vec<bool>> + Copy,
+        G: Lens<Target = [f32; HeadField::ALL.len()]> + Copy,
+    {
+        Self.build(cx, |cx| {
+            //Note N on the channel plays head N, channel and first note are shown 1 based and as
+            //MIDI note numbers
+            HStack::new(cx, |cx| {
+                Checkbox::new(cx, settings.map(|settings| settings.note_mode.enabled))
+                    .on_toggle(|cx| {
+                        cx.emit(OsClaPhotonEditorEvent::ToggleNoteMode);
+                    });
+                Label::new(cx, "Note Mode").class("label");
+                Textbox::new(cx, settings.map(|settings| settings.note_mode.channel + 1))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<u8>() {
+                            Ok(val) if (1..=16).contains(&val) => {
+                                cx.emit(OsClaPhotonEditorEvent::SetNoteChannel(val - 1));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::NoteModeChange);
+                    })
+                    .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| settings.note_mode.first_note))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<u8>() {
+                            Ok(val) if val < 128 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetNoteFirst(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::NoteModeChange);
+                    })
+                    .width(Pixels(60.0));
+                Checkbox::new(cx, settings.map(|settings| settings.note_mode.hue))
+                    .on_toggle(|cx| {
+                        cx.emit(OsClaPhotonEditorEvent::ToggleNoteHue);
+                    });
+                Label::new(cx, "Note Hue").class("label");
+            })
+            .class("row");
+            //Attack, hold and release in seconds
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Envelope").class("label");
+                Textbox::new(cx, settings.map(|settings| settings.note_mode.attack))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val >= 0.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetNoteAttack(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::NoteModeChange);
+                    })
+                    .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| settings.note_mode.hold))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val >= 0.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetNoteHold(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::NoteModeChange);
+                    })
+                    .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| settings.note_mode.release))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val >= 0.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetNoteRelease(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::NoteModeChange);
+                    })
+                    .width(Pixels(60.0));
+            })
+            .class("row");
+            //One band per head, spaced evenly in octaves from low to high Hz
+            HStack::new(cx, |cx| {
+                Checkbox::new(cx, settings.map(|settings| settings.analyser.enabled))
+                    .on_toggle(|cx| {
+                        cx.emit(OsClaPhotonEditorEvent::ToggleAnalyser);
+                    });
+                Label::new(cx, "Analyser").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::CycleAnalyserTarget),
+                    move |cx| Label::new(cx, settings.map(|settings| settings.analyser.target.name().to_string())),
+                )
+                .width(Pixels(70.0));
+                Textbox::new(cx, settings.map(|settings| settings.analyser.low))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val >= 1.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetAnalyserLow(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::AnalyserChange);
+                    })
+                    .width(Pixels(60.0));
+                Textbox::new(cx, settings.map(|settings| settings.analyser.high))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val >= 1.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetAnalyserHigh(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::AnalyserChange);
+                    })
+                    .width(Pixels(60.0));
+            })
+            .class("row");
+            //Range in dB, the smoothing and gain of each band are under its head
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Bands").class("label");
+                Textbox::new(cx, settings.map(|settings| settings.analyser.range))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val < 0.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetAnalyserRange(val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::AnalyserChange);
+                    })
+                    .width(Pixels(60.0));
+            })
+            .class("row");
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Selection").class("label");
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::SelectAllHeads(true)),
+                    |cx| Label::new(cx, "All"),
+                )
+                .width(Pixels(60.0));
+                Button::new(
+                    cx,
+                    |cx| cx.emit(OsClaPhotonEditorEvent::SelectAllHeads(false)),
+                    |cx| Label::new(cx, "None"),
+                )
+                .width(Pixels(60.0));
+            })
+            .class("row");
+            //Group sliders, moving one sets that field on every selected head. They show the first
+            //selected head
+            for field in HeadField::ALL {
+                HStack::new(cx, |cx| {
+                    Label::new(cx, field.name()).class("label");
+                    Slider::new(cx, group_values.map(move |values| values[field as usize]))
+                        .on_changing(move |cx, value| {
+                            cx.emit(OsClaPhotonEditorEvent::SetHeadsField(field, value));
+                        })
+                        .width(Pixels(300.0));
+                })
+                .class("row");
+            }
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Palette").class("label");
+                for color in PALETTE {
+                    Button::new(
+                        cx,
+                        move |cx| cx.emit(OsClaPhotonEditorEvent::SetHeadsColor(color)),
+                        |cx| Label::new(cx, ""),
+                    )
+                    .background_color(swatch_color(color))
+                    .width(Pixels(30.0));
+                }
+                //Any colour, shows the one of the group sliders
+                Textbox::new(cx, group_values.map(|values| {
+                    hex_color([
+                        values[HeadField::Red as usize],
+                        values[HeadField::Green as usize],
+                        values[HeadField::Blue as usize],
+                    ])
+                }))
+                .on_edit(move |cx, text| {
+                    cx.toggle_class("invalid", parse_hex_color(&text).is_none());
+                })
+                .on_submit(|cx, text, _| {
+                    if let Some(color) = parse_hex_color(&text) {
+                        cx.emit(OsClaPhotonEditorEvent::SetHeadsColor(color));
+                    }
+                })
+                .width(Pixels(80.0));
+            })
+            .class("row");
+            //One column per head, scrolls sideways for the larger builds
+            ScrollView::new(cx, 0.0, 0.0, true, false, move |cx| {
+                HStack::new(cx, move |cx| {
+                    for index in 0..NUM_PARAMS {
+                        HeadColumn::new(cx, params, settings, selected_heads, index);
+                    }
+                });
+            })
+            .height(Pixels(300.0));
+        })
+    }
+}
+
+impl View for HeadView {
+    fn element(&self) -> Option<&'static str> {
+        Some("generic-ui")
+    }
+}
+
+/// A single head: select, colour swatch, tilt, dimmer and the smoothing and gain of its analyser
+/// band
+pub struct HeadColumn;
+
+impl HeadColumn {
+    pub fn new<P, T, S>(cx: &mut Context, params: P, settings: T, selected_heads: S, index: usize) -> Handle<Self>
+    where
+        P: Lens<Target = Arc<OsClaPhotonParams>> + Copy,
+        T: Lens<Target = OscSettings> + Copy,
+        S: Lens<Target = Vec<bool>> + Copy,
+    {
+        Self.build(cx, |cx| {
+            VStack::new(cx, |cx| {
+                HStack::new(cx, |cx| {
+                    Checkbox::new(cx, selected_heads.map(move |selected| {
+                        selected.get(index).copied().unwrap_or(false)
+                    }))
+                        .on_toggle(move |cx| {
+                            cx.emit(OsClaPhotonEditorEvent::ToggleHeadSelected(index));
+                        });
+                    Label::new(cx, (index + 1).to_string()).class("label");
+                });
+                //Shows the current colour, clicking it selects only this head
+                Button::new(
+                    cx,
+                    move |cx| cx.emit(OsClaPhotonEditorEvent::SelectOnlyHead(index)),
+                    |cx| Label::new(cx, ""),
+                )
+                .background_color(params.map(move |params| {
+                    let tilt_params = &params.tilt_params[index];
+                    swatch_color([
+                        tilt_params.red.value(),
+                        tilt_params.green.value(),
+                        tilt_params.blue.value(),
+                    ])
+                }))
+                .width(Stretch(1.0))
+                .height(Pixels(40.0));
+                Label::new(cx, HeadField::Tilt.name()).class("label");
+                ParamSlider::new(cx, params, move |params| &params.tilt_params[index].tilt);
+                Label::new(cx, HeadField::Dimmer.name()).class("label");
+                ParamSlider::new(cx, params, move |params| &params.tilt_params[index].dimmer);
+                Label::new(cx, "Band s").class("label");
+                Textbox::new(cx, settings.map(move |settings| settings.analyser.smoothing[index]))
+                    .on_edit(move |cx, text| {
+                        match text.parse::<f32>() {
+                            Ok(val) if val >= 0.0 => {
+                                cx.emit(OsClaPhotonEditorEvent::SetBandSmoothing(index, val));
+                                cx.toggle_class("invalid", false);
+                            }
+                            _ => cx.toggle_class("invalid", true),
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::AnalyserChange);
+                    })
+                    .width(Stretch(1.0));
+                Label::new(cx, "Band dB").class("label");
+                Textbox::new(cx, settings.map(move |settings| settings.analyser.gains[index]))
+                    .on_edit(move |cx, text| {
+                        if let Ok(val) = text.parse::<f32>() {
+                            cx.emit(OsClaPhotonEditorEvent::SetBandGain(index, val));
+                            cx.toggle_class("invalid", false);
+                        } else {
+                            cx.toggle_class("invalid", true);
+                        }
+                    })
+                    .on_submit(|cx,  _, _| {
+                        cx.emit(OsClaPhotonEditorEvent::AnalyserChange);
+                    })
+                    .width(Stretch(1.0));
+            })
+            .width(Pixels(90.0));
+        })
+    }
+}
+
+impl View for HeadColumn {
+    fn element(&self) -> Option<&'static str> {
+        Some("generic-ui")
+    }
+}
@@ -0,0 +1,147 @@
+use nih_plug::prelude::Transport;
+use rosc::OscTime;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds between the NTP epoch (1900) and the UNIX epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+/// 2^32, the resolution of the fractional part of an NTP timestamp
+const NTP_FRACTION_SCALE: f64 = 4_294_967_296.0;
+/// How far the sample clock is allowed to drift from the wall clock before we re-anchor, on top
+/// of the jitter a full host buffer can add. This covers xruns and hosts that stop calling
+/// process() for a while
+const DRIFT_TOLERANCE: f64 = 0.050;
+
+/// The special OSC timetag meaning "process immediately"
+pub const OSC_TIME_IMMEDIATELY: OscTime = OscTime {
+    seconds: 0,
+    fractional: 1,
+};
+
+/// Counts every sample that goes through process() and maps that count onto the wall clock.
+///
+/// With `SAMPLE_ACCURATE_AUTOMATION` nih-plug splits the host buffer at every automation point
+/// and calls process() once per split, so the running sample count at the start of a call is
+/// the exact sample offset of that automation point. Converting it with the sample rate gives
+/// every block its own timetag, even when the host runs large buffers.
+#[derive(Default)]
+pub struct SampleClock {
+    sample_rate: f64,
+    /// Samples processed since the last reset, this is where the current block starts
+    samples: u64,
+    /// (wall clock seconds, sample count) at the last anchor point
+    anchor: Option<(f64, u64)>,
+    drift_tolerance: f64,
+}
+
+impl SampleClock {
+    pub fn reset(&mut self, sample_rate: f32, max_buffer_size: u32) {
+        self.sample_rate = sample_rate as f64;
+        self.samples = 0;
+        self.anchor = None;
+        self.drift_tolerance = DRIFT_TOLERANCE + 2.0 * max_buffer_size as f64 / self.sample_rate;
+    }
+
+    /// Wall clock time (UNIX seconds) the current block should go out at, or `None` when the
+    /// transport is stopped and changes should be sent immediately.
+    pub fn send_at(&mut self, transport: &Transport) -> Option<f64> {
+        self.send_at_time(transport.playing, unix_now_secs())
+    }
+
+    /// `send_at()` with the wall clock passed in
+    fn send_at_time(&mut self, playing: bool, now: f64) -> Option<f64> {
+        if !playing || self.sample_rate <= 0.0 {
+            self.anchor = None;
+            return None;
+        }
+
+        let send_at = match self.anchor {
+            Some((anchor_wall, anchor_samples)) => {
+                let send_at = anchor_wall
+                    + self.samples.saturating_sub(anchor_samples) as f64 / self.sample_rate;
+                if (send_at - now).abs() > self.drift_tolerance {
+                    self.anchor = Some((now, self.samples));
+                    now
+                } else {
+                    send_at
+                }
+            }
+            None => {
+                self.anchor = Some((now, self.samples));
+                now
+            }
+        };
+        Some(send_at)
+    }
+
+    /// Move the clock past the block that was just processed
+    pub fn advance(&mut self, num_samples: usize) {
+        self.samples += num_samples as u64;
+    }
+}
+
+pub fn unix_now_secs() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+pub fn ntp_from_unix_secs(unix_secs: f64) -> OscTime {
+    let whole = unix_secs.trunc();
+    OscTime {
+        //NTP era 0 wraps in 2036, let it wrap like the spec says
+        seconds: (whole as u64 + NTP_UNIX_OFFSET) as u32,
+        fractional: ((unix_secs - whole) * NTP_FRACTION_SCALE) as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ntp_timestamps_count_from_1900() {
+        let epoch = ntp_from_unix_secs(0.0);
+        assert_eq!((epoch.seconds, epoch.fractional), (NTP_UNIX_OFFSET as u32, 0));
+        let half = ntp_from_unix_secs(1.5);
+        assert_eq!((half.seconds, half.fractional), (NTP_UNIX_OFFSET as u32 + 1, 1 << 31));
+        //Era 0 ends on 2036-02-07 06:28:16 UTC
+        let wrapped = ntp_from_unix_secs((1u64 << 32) as f64 - NTP_UNIX_OFFSET as f64 + 2.0);
+        assert_eq!(wrapped.seconds, 2);
+    }
+
+    #[test]
+    fn blocks_follow_the_sample_count() {
+        let mut clock = SampleClock::default();
+        clock.reset(48000.0, 512);
+        assert_eq!(clock.send_at_time(true, 100.0), Some(100.0));
+        clock.advance(24000);
+        //Wall clock jitter within the tolerance doesn't move the block
+        assert_eq!(clock.send_at_time(true, 100.51), Some(100.5));
+        clock.advance(24000);
+        assert_eq!(clock.send_at_time(true, 100.99), Some(101.0));
+    }
+
+    #[test]
+    fn drift_re_anchors_on_the_wall_clock() {
+        let mut clock = SampleClock::default();
+        clock.reset(48000.0, 512);
+        clock.send_at_time(true, 100.0);
+        clock.advance(24000);
+        //The host stopped calling process() for a second
+        assert_eq!(clock.send_at_time(true, 101.5), Some(101.5));
+        clock.advance(24000);
+        assert_eq!(clock.send_at_time(true, 102.0), Some(102.0));
+    }
+
+    #[test]
+    fn stopped_transport_sends_immediately_and_re_anchors() {
+        let mut clock = SampleClock::default();
+        clock.reset(48000.0, 512);
+        clock.send_at_time(true, 100.0);
+        clock.advance(24000);
+        assert_eq!(clock.send_at_time(false, 100.5), None);
+        clock.advance(24000);
+        assert_eq!(clock.send_at_time(true, 200.0), Some(200.0));
+    }
+}
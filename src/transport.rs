@@ -0,0 +1,466 @@
+use nih_plug::debug::*;
+use serde::{Deserialize, Serialize};
+use socket2::SockRef;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long we wait for a TCP connection before giving up until the next retry
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long a write may block on a stalled peer before the connection counts as lost
+const TCP_WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+/// Reconnect backoff, doubles after every failed attempt up to the max
+const TCP_RETRY_MIN: Duration = Duration::from_millis(250);
+const TCP_RETRY_MAX: Duration = Duration::from_secs(5);
+
+/// SLIP special bytes (RFC 1055)
+const SLIP_END: u8 = 0xC0;
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+/// How packets get to TD-Photon
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OscTransport {
+    UdpUnicast,
+    UdpBroadcast,
+    UdpMulticast,
+    /// Reliable delivery, reconnects on its own if the receiver goes away
+    Tcp,
+}
+
+impl OscTransport {
+    pub const ALL: [OscTransport; 4] = [
+        OscTransport::UdpUnicast,
+        OscTransport::UdpBroadcast,
+        OscTransport::UdpMulticast,
+        OscTransport::Tcp,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OscTransport::UdpUnicast => "UDP Unicast",
+            OscTransport::UdpBroadcast => "UDP Broadcast",
+            OscTransport::UdpMulticast => "UDP Multicast",
+            OscTransport::Tcp => "TCP",
+        }
+    }
+
+    /// The next transport in the list, wraps around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|t| *t == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// How OSC packets are delimited on a TCP stream
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TcpFraming {
+    /// OSC 1.1, double ended SLIP
+    Slip,
+    /// OSC 1.0, int32 big endian size before every packet
+    LengthPrefix,
+}
+
+impl TcpFraming {
+    pub fn name(self) -> &'static str {
+        match self {
+            TcpFraming::Slip => "SLIP (OSC 1.1)",
+            TcpFraming::LengthPrefix => "Length Prefix (OSC 1.0)",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            TcpFraming::Slip => TcpFraming::LengthPrefix,
+            TcpFraming::LengthPrefix => TcpFraming::Slip,
+        }
+    }
+}
+
+/// Multicast settings, only used with `OscTransport::UdpMulticast`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OscMulticast {
+    /// The group we send to and join for feedback, replaces the main address
+    pub group: String,
+    /// Outgoing interface as an IP or a name like `eth1`, empty lets the OS pick
+    pub interface: String,
+    pub ttl: u32,
+    /// Also deliver to receivers on this machine
+    pub loopback: bool,
+}
+
+impl Default for OscMulticast {
+    fn default() -> Self {
+        Self {
+            group: "239.255.0.1".to_string(),
+            interface: String::new(),
+            ttl: 1,
+            loopback: true,
+        }
+    }
+}
+
+impl OscMulticast {
+    pub fn group_addr(&self) -> Option<IpAddr> {
+        match self.group.parse::<IpAddr>() {
+            Ok(addr) if addr.is_multicast() => Some(addr),
+            _ => {
+                nih_error!("{} is not a multicast group", self.group);
+                None
+            }
+        }
+    }
+
+    /// The interface IPv4 address, from either an IP or an interface name
+    pub fn interface_v4(&self) -> Option<Ipv4Addr> {
+        if self.interface.is_empty() {
+            return None;
+        }
+        if let Ok(addr) = self.interface.parse::<Ipv4Addr>() {
+            return Some(addr);
+        }
+        let interfaces = match if_addrs::get_if_addrs() {
+            Ok(interfaces) => interfaces,
+            Err(e) => {
+                nih_error!("Failed to list network interfaces {:?}", e);
+                return None;
+            }
+        };
+        let addr = interfaces
+            .into_iter()
+            .filter(|interface| interface.name == self.interface)
+            .find_map(|interface| match interface.ip() {
+                IpAddr::V4(addr) => Some(addr),
+                IpAddr::V6(_) => None,
+            });
+        if addr.is_none() {
+            nih_error!("No IPv4 network interface named {}", self.interface);
+        }
+        addr
+    }
+}
+
+/// What the editor makes of a typed address before anything touches the network
+#[derive(Clone, Debug, PartialEq)]
+pub enum HostInput {
+    Ip(IpAddr),
+    /// Looks like a hostname, only resolving it tells if it exists
+    Hostname,
+    /// Why it can't be either
+    Invalid(String),
+}
+
+/// IPv4 or IPv6 address, otherwise a hostname following RFC 1123
+pub fn parse_host(text: &str) -> HostInput {
+    if let Ok(addr) = text.parse::<IpAddr>() {
+        return HostInput::Ip(addr);
+    }
+    if text.is_empty() {
+        return HostInput::Invalid("address is empty".to_string());
+    }
+    let valid_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    //All digits and dots is a mistyped IPv4 address, not a hostname
+    if text.len() > 253
+        || !text.trim_end_matches('.').split('.').all(valid_label)
+        || text.chars().all(|c| c.is_ascii_digit() || c == '.')
+    {
+        return HostInput::Invalid(format!("{} is not an IP address or hostname", text));
+    }
+    HostInput::Hostname
+}
+
+/// Blocking DNS lookup, keep it off the GUI and audio threads
+pub fn resolve_host(host: &str, port: u16) -> Result<SocketAddr, String> {
+    match (host, port).to_socket_addrs() {
+        Ok(mut addrs) => addrs
+            .next()
+            .ok_or_else(|| format!("{} has no addresses", host)),
+        Err(e) => Err(format!("{} {}", host, e)),
+    }
+}
+
+/// The sockets all UDP links share, one per address family since a socket bound to 0.0.0.0
+/// can't send to IPv6 destinations
+pub struct UdpSockets {
+    pub v4: UdpSocket,
+    /// None if the system has no IPv6
+    pub v6: Option<UdpSocket>,
+}
+
+impl UdpSockets {
+    /// Only the IPv4 socket is required, IPv6 destinations fail on their own without the other
+    pub fn bind() -> io::Result<Self> {
+        let v4 = UdpSocket::bind("0.0.0.0:0")?;
+        let v6 = match UdpSocket::bind("[::]:0") {
+            Ok(socket) => Some(socket),
+            Err(e) => {
+                nih_error!("Failed to bind IPv6 socket {:?}", e);
+                None
+            }
+        };
+        Ok(Self { v4, v6 })
+    }
+
+    /// Apply the transport to both sockets
+    pub fn configure(&self, transport: OscTransport, multicast: &OscMulticast) {
+        configure_udp_socket(&self.v4, transport, multicast);
+        if let Some(v6) = &self.v6 {
+            configure_udp_socket_v6(v6, transport, multicast);
+        }
+    }
+
+    fn for_addr(&self, addr: SocketAddr) -> io::Result<&UdpSocket> {
+        match addr {
+            SocketAddr::V4(_) => Ok(&self.v4),
+            SocketAddr::V6(_) => self
+                .v6
+                .as_ref()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::AddrNotAvailable, "no IPv6 socket")),
+        }
+    }
+}
+
+/// Apply the transport to the IPv4 socket
+pub fn configure_udp_socket(socket: &UdpSocket, transport: OscTransport, multicast: &OscMulticast) {
+    let broadcast = transport == OscTransport::UdpBroadcast;
+    if let Err(e) = socket.set_broadcast(broadcast) {
+        nih_error!("Failed to set broadcast to {} {:?}", broadcast, e);
+    }
+    if transport != OscTransport::UdpMulticast {
+        return;
+    }
+
+    let ttl_result = socket.set_multicast_ttl_v4(multicast.ttl);
+    if ttl_result.is_err() {
+        nih_error!("Failed to set multicast TTL {:?}", ttl_result.unwrap_err());
+    }
+    let loop_result = socket.set_multicast_loop_v4(multicast.loopback);
+    if loop_result.is_err() {
+        nih_error!("Failed to set multicast loopback {:?}", loop_result.unwrap_err());
+    }
+    //Always set the interface so clearing the field goes back to the default one
+    let interface = multicast.interface_v4().unwrap_or(Ipv4Addr::UNSPECIFIED);
+    let interface_result = SockRef::from(socket).set_multicast_if_v4(&interface);
+    if interface_result.is_err() {
+        nih_error!(
+            "Failed to set multicast interface {} {:?}",
+            interface,
+            interface_result.unwrap_err()
+        );
+    }
+}
+
+/// IPv6 has no broadcast and the interface is left to the OS, so only hops and loopback apply
+fn configure_udp_socket_v6(socket: &UdpSocket, transport: OscTransport, multicast: &OscMulticast) {
+    if transport != OscTransport::UdpMulticast {
+        return;
+    }
+    let hops_result = SockRef::from(socket).set_multicast_hops_v6(multicast.ttl);
+    if hops_result.is_err() {
+        nih_error!("Failed to set multicast hops {:?}", hops_result.unwrap_err());
+    }
+    let loop_result = socket.set_multicast_loop_v6(multicast.loopback);
+    if loop_result.is_err() {
+        nih_error!("Failed to set multicast loopback {:?}", loop_result.unwrap_err());
+    }
+}
+
+/// Join the multicast group on a listening socket so feedback sent to the group reaches us
+pub fn join_multicast(socket: &UdpSocket, multicast: &OscMulticast) {
+    let join_result = match multicast.group_addr() {
+        Some(IpAddr::V4(group)) => socket.join_multicast_v4(
+            &group,
+            &multicast.interface_v4().unwrap_or(Ipv4Addr::UNSPECIFIED),
+        ),
+        //0 lets the OS pick the interface
+        Some(IpAddr::V6(group)) => socket.join_multicast_v6(&group, 0),
+        None => return,
+    };
+    match join_result {
+        Ok(_) => nih_trace!("Joined multicast group {}", multicast.group),
+        Err(e) => nih_error!("Failed to join multicast group {} {:?}", multicast.group, e),
+    }
+}
+
+/// The connection to one destination. UDP links just remember the address, TCP links own a
+/// stream and reconnect with a backoff when it breaks. Connecting happens on its own thread so
+/// a dead peer never holds up the worker, call `poll_connect()` to drive it.
+pub struct OscLink {
+    addr: SocketAddr,
+    stream: Option<TcpStream>,
+    connecting: Option<JoinHandle<io::Result<TcpStream>>>,
+    retry_at: Option<Instant>,
+    retry_delay: Duration,
+}
+
+impl OscLink {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            stream: None,
+            connecting: None,
+            retry_at: None,
+            retry_delay: TCP_RETRY_MIN,
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Drop the TCP stream, e.g. when the transport changes. A connect still running finishes
+    /// on its own and is thrown away
+    pub fn disconnect(&mut self) {
+        self.stream = None;
+        self.connecting = None;
+        self.retry_at = None;
+        self.retry_delay = TCP_RETRY_MIN;
+    }
+
+    /// Pick up a finished connect, or start one once the backoff has passed. Never blocks,
+    /// returns true when the stream has just come up
+    pub fn poll_connect(&mut self) -> bool {
+        if self.stream.is_some() {
+            return false;
+        }
+        if self.connecting.as_ref().map_or(false, |connecting| connecting.is_finished()) {
+            let connect_result = match self.connecting.take().map(JoinHandle::join) {
+                Some(Ok(connect_result)) => connect_result,
+                _ => Err(io::Error::new(io::ErrorKind::Other, "connect thread panicked")),
+            };
+            match connect_result {
+                Ok(stream) => {
+                    let _ = stream.set_nodelay(true);
+                    if let Err(e) = stream.set_write_timeout(Some(TCP_WRITE_TIMEOUT)) {
+                        nih_error!("Failed to set TCP write timeout {:?}", e);
+                    }
+                    nih_trace!("TCP connected to {}", self.addr);
+                    self.stream = Some(stream);
+                    self.retry_at = None;
+                    self.retry_delay = TCP_RETRY_MIN;
+                    return true;
+                }
+                Err(e) => {
+                    nih_trace!("TCP connect to {} failed {:?}", self.addr, e);
+                    self.retry_at = Some(Instant::now() + self.retry_delay);
+                    self.retry_delay = (self.retry_delay * 2).min(TCP_RETRY_MAX);
+                }
+            }
+        }
+        let retry_due = self.retry_at.map_or(true, |retry_at| Instant::now() >= retry_at);
+        if self.connecting.is_none() && retry_due {
+            let addr = self.addr;
+            self.connecting = Some(thread::spawn(move || {
+                TcpStream::connect_timeout(&addr, TCP_CONNECT_TIMEOUT)
+            }));
+        }
+        false
+    }
+
+    pub fn send(
+        &mut self,
+        udp: &UdpSockets,
+        transport: OscTransport,
+        framing: TcpFraming,
+        packet: &[u8],
+    ) -> io::Result<usize> {
+        match transport {
+            OscTransport::UdpUnicast | OscTransport::UdpBroadcast | OscTransport::UdpMulticast => {
+                udp.for_addr(self.addr)?.send_to(packet, self.addr)
+            }
+            OscTransport::Tcp => self.send_tcp(framing, packet),
+        }
+    }
+
+    /// Only writes to a connected stream, the caller polls the connect
+    fn send_tcp(&mut self, framing: TcpFraming, packet: &[u8]) -> io::Result<usize> {
+        let mut framed = Vec::with_capacity(packet.len() + 8);
+        match framing {
+            TcpFraming::Slip => slip_encode(packet, &mut framed),
+            TcpFraming::LengthPrefix => {
+                framed.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+                framed.extend_from_slice(packet);
+            }
+        }
+        let write_result = match &mut self.stream {
+            Some(stream) => stream.write_all(&framed),
+            None => return Err(io::ErrorKind::NotConnected.into()),
+        };
+        match write_result {
+            Ok(_) => Ok(packet.len()),
+            Err(e) => {
+                //Broken pipe, reset or a stalled peer. A timed out write may have left half a
+                //packet on the stream, so start over with a new one
+                nih_error!("TCP connection to {} lost {:?}", self.addr, e);
+                self.stream = None;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Double ended SLIP, an END before and after the packet
+pub fn slip_encode(packet: &[u8], out: &mut Vec<u8>) {
+    out.push(SLIP_END);
+    for byte in packet {
+        match *byte {
+            SLIP_END => out.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+            SLIP_ESC => out.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+            byte => out.push(byte),
+        }
+    }
+    out.push(SLIP_END);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slip_escapes_end_and_esc_bytes() {
+        let mut out = Vec::new();
+        slip_encode(&[0x01, SLIP_END, 0x02, SLIP_ESC, 0x03], &mut out);
+        assert_eq!(
+            out,
+            vec![SLIP_END, 0x01, SLIP_ESC, SLIP_ESC_END, 0x02, SLIP_ESC, SLIP_ESC_ESC, 0x03, SLIP_END]
+        );
+    }
+
+    #[test]
+    fn slip_leaves_the_escape_results_alone() {
+        let mut out = Vec::new();
+        slip_encode(&[SLIP_ESC_END, SLIP_ESC_ESC], &mut out);
+        assert_eq!(out, vec![SLIP_END, SLIP_ESC_END, SLIP_ESC_ESC, SLIP_END]);
+    }
+
+    #[test]
+    fn parse_host_takes_ips_and_hostnames() {
+        assert_eq!(parse_host("127.0.0.1"), HostInput::Ip("127.0.0.1".parse().unwrap()));
+        assert_eq!(parse_host("::1"), HostInput::Ip("::1".parse().unwrap()));
+        assert_eq!(parse_host("localhost"), HostInput::Hostname);
+        assert_eq!(parse_host("td-photon.local."), HostInput::Hostname);
+    }
+
+    #[test]
+    fn parse_host_rejects_typos() {
+        for text in ["", "192.168.1.300", "-photon", "photon-", "td photon", "a..b"] {
+            assert!(
+                matches!(parse_host(text), HostInput::Invalid(_)),
+                "{:?} was accepted",
+                text
+            );
+        }
+        assert!(matches!(parse_host(&"a".repeat(64)), HostInput::Invalid(_)));
+    }
+}